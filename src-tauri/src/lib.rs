@@ -1,11 +1,17 @@
-use std::process::Command;
 use std::path::{Path, PathBuf};
 use std::fs;
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
-use std::collections::HashMap;
+use fs2::FileExt;
+use tauri_plugin_opener::OpenerExt;
+use std::collections::{HashMap, HashSet};
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use futures_util::StreamExt;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 struct VideoRecord {
     id: String,
     url: String,
@@ -19,528 +25,6696 @@ struct VideoRecord {
     summary_content: Option<String>,
     created_at: String,
     updated_at: String,
+    #[serde(default)]
+    whisper_model: Option<String>,
+    /// 指定的转录语言；未指定时为 whisper 自动检测到的语言（仅在有 json 输出时可知）
+    #[serde(default)]
+    transcribe_language: Option<String>,
+    #[serde(default)]
+    subtitle_files: HashMap<String, String>,
+    /// task 为 translate 时 whisper 译成的英文文本；transcript_content 始终是原语言的转录
+    #[serde(default)]
+    translation_content: Option<String>,
+    #[serde(default)]
+    translation_file: Option<String>,
+    #[serde(default)]
+    audio_format: Option<String>,
+    #[serde(default)]
+    duration_seconds: Option<u64>,
+    #[serde(default)]
+    audio_size_bytes: Option<u64>,
+    /// 音频文件内容的 SHA-256，用于识别不同链接下的相同视频
+    #[serde(default)]
+    audio_sha256: Option<String>,
+    /// 转录后按 keep_audio 删除了音频：downloaded 仍为 true 但 audio_file 为空，重新转录时需要重新下载
+    #[serde(default)]
+    audio_purged: bool,
+    /// 下载时是否使用了 cookies 认证（只记录标记，不保存 cookies 内容）
+    #[serde(default)]
+    authenticated_download: bool,
+    /// 下载时使用的 yt-dlp 格式选择器，便于按相同条件重新下载
+    #[serde(default)]
+    format_selector: Option<String>,
+    /// 下载时是否使用了自定义 User-Agent 或请求头（只记录标记，不保存具体的值）
+    #[serde(default)]
+    custom_headers: bool,
+    #[serde(default)]
+    thumbnail_file: Option<String>,
+    /// keep_video 时保留的视频文件
+    #[serde(default)]
+    video_file: Option<String>,
+    /// yt-dlp 导出的完整元数据（上传者、发布日期、章节等），仅在 save_metadata 时保存
+    #[serde(default)]
+    metadata_file: Option<String>,
+    /// 转录文本的词数（中日韩文字按字计）
+    #[serde(default)]
+    word_count: Option<usize>,
+    /// 按每分钟 200 词估算的阅读时间
+    #[serde(default)]
+    reading_minutes: Option<u32>,
+    /// 生成总结所用的服务，未调用 API 时为 simple
+    #[serde(default)]
+    summary_provider: Option<String>,
+    /// 生成总结所用的模型，未调用 API 时为空
+    #[serde(default)]
+    summary_model: Option<String>,
+    /// 生成总结所用的预设风格，使用自定义提示词或未调用 API 时为空
+    #[serde(default)]
+    summary_style: Option<SummaryStyle>,
+    /// 内容的关键词或主题标签
+    #[serde(default)]
+    keywords: Option<Vec<String>>,
+    /// 请求了多种总结语言时按语言保存的各份总结，summary_content 为其中的主语言
+    #[serde(default)]
+    summaries: Option<HashMap<String, String>>,
+    /// 带时间戳的转录分段，仅在请求 include_segments 时生成
+    #[serde(default)]
+    segments: Option<Vec<TranscriptSegment>>,
+    /// 按 yt-dlp 元数据中的章节逐章生成的总结，需要 include_segments 和 save_metadata；
+    /// 视频没有章节时为空，只有整体总结
+    #[serde(default)]
+    chapter_summaries: Option<Vec<ChapterSummary>>,
+    /// 分段转录进行中的进度，全部完成后清空
+    #[serde(default)]
+    chunked_transcription: Option<ChunkedTranscription>,
+    /// yt-dlp 提供的发布日期，格式为 YYYYMMDD
+    #[serde(default)]
+    upload_date: Option<String>,
+    #[serde(default)]
+    uploader: Option<String>,
+    /// 只下载了视频中的这一段
+    #[serde(default)]
+    time_range: Option<TimeRange>,
+    /// 视频目录相对 vault 的路径，如 `2024-01-31/<id>`；为空时是旧的平铺布局 `<id>`
+    #[serde(default)]
+    video_dir: Option<String>,
+    /// 用户为视频写的备注
+    #[serde(default)]
+    notes: Option<String>,
+    /// 用户添加的分类标签，与自动提取的 keywords 分开保存
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Vault {
-    videos: HashMap<String, VideoRecord>,
+/// 分段转录的中间结果，每完成一段就写入 vault，重新运行时跳过已完成的段
+#[derive(Serialize, Deserialize, Clone)]
+struct ChunkedTranscription {
+    chunk_seconds: u64,
+    /// 已完成的各段，按序号排列
+    chunks: Vec<ChunkTranscript>,
 }
 
-#[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+#[derive(Serialize, Deserialize, Clone)]
+struct ChunkTranscript {
+    index: usize,
+    text: String,
+    /// 已加上该段在整段音频中的偏移
+    segments: Vec<TranscriptSegment>,
+    #[serde(default)]
+    detected_language: Option<String>,
 }
 
-fn generate_video_id(url: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(url.as_bytes());
-    let result = hasher.finalize();
-    format!("{:x}", result)[..16].to_string() // 取前16位作为ID
+/// whisper 识别出的一个片段，时间单位为秒
+#[derive(Serialize, Deserialize, Clone)]
+struct TranscriptSegment {
+    start: f64,
+    end: f64,
+    text: String,
+    /// 说话人识别开启时 whisperx 标注的说话人，如 `SPEAKER_00`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    speaker: Option<String>,
+    /// Python 版 whisper 给出的片段平均对数概率，越接近 0 越可信；whisper.cpp 的输出没有这一项
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    avg_logprob: Option<f64>,
+    /// 片段实际为静音的概率，很高时文本往往是凭空生成的
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    no_speech_prob: Option<f64>,
+    /// avg_logprob 低于 min_confidence，文本可能不可靠
+    #[serde(default)]
+    low_confidence: bool,
 }
 
-fn expand_tilde_path(path: &str) -> String {
-    if path.starts_with("~/") {
-        if let Some(home_dir) = std::env::var_os("HOME") {
-            let home_path = std::path::Path::new(&home_dir);
-            return home_path.join(&path[2..]).to_string_lossy().to_string();
-        }
-    }
-    path.to_string()
+/// 一个章节的总结，start / end 为章节在原视频中的时间，单位为秒
+#[derive(Serialize, Deserialize, Clone)]
+struct ChapterSummary {
+    title: String,
+    start: f64,
+    end: f64,
+    summary: String,
 }
 
-fn get_vault_path(base_path: &str) -> PathBuf {
-    PathBuf::from(base_path).join("video-transcriber-vault")
+/// vault.toml 的结构版本，记录字段有不兼容的变化时递增，并在 migrate_vault 中补上升级步骤
+const VAULT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Vault {
+    /// 早期的 vault.toml 没有版本号，按 0 处理
+    #[serde(default)]
+    version: u32,
+    videos: HashMap<String, VideoRecord>,
 }
 
-fn get_vault_config_path(vault_path: &PathBuf) -> PathBuf {
-    vault_path.join("vault.toml")
+/// vault 级别的默认设置，保存在 config.toml 中，与存放视频记录的 vault.toml 分开
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct VaultConfig {
+    api_provider: Option<String>,
+    whisper_model: Option<String>,
+    audio_format: Option<String>,
+    language: Option<String>,
+    proxy: Option<String>,
+    yt_dlp_path: Option<String>,
+    whisper_path: Option<String>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    dir_layout: Option<VideoDirLayout>,
+    rate_limit: Option<String>,
+    sleep_interval: Option<u32>,
+    max_sleep_interval: Option<u32>,
+    /// whisperx 说话人识别所需的 Hugging Face 访问令牌
+    hf_token: Option<String>,
+    /// 本机转录使用的计算设备：cpu、cuda 或 mps
+    device: Option<String>,
+    fp16: Option<bool>,
+    /// 流水线结束后通知的地址
+    webhook_url: Option<String>,
 }
 
-fn get_video_dir_path(vault_path: &PathBuf, video_id: &str) -> PathBuf {
-    vault_path.join(video_id)
+/// 新视频目录在 vault 中的组织方式，已有目录需要通过 migrate_vault_layout 迁移
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum VideoDirLayout {
+    /// `vault/<id>/`
+    #[default]
+    Flat,
+    /// `vault/<发布日期>/<id>/`
+    ByDate,
+    /// `vault/<上传者>/<id>/`
+    ByUploader,
 }
 
-fn load_vault(vault_path: &PathBuf) -> Result<Vault, String> {
-    let config_path = get_vault_config_path(vault_path);
-    
-    if !config_path.exists() {
-        // 创建新的vault
-        return Ok(Vault {
-            videos: HashMap::new(),
-        });
+/// 命令返回给前端的错误，序列化为 `{ kind, message }`，前端可按 kind 区分处理
+#[derive(Debug)]
+enum PipelineError {
+    /// yt-dlp / whisper 等外部工具未安装或无法运行
+    ToolNotFound(String),
+    /// 调用参数不合法
+    InvalidInput(String),
+    /// 请求的视频或流水线不存在
+    NotFound(String),
+    /// 同一视频已有流水线在运行
+    AlreadyRunning(String),
+    Cancelled,
+    DownloadFailed(String),
+    /// 视频有年龄限制，需要已登录账号的 cookies
+    AgeRestricted(String),
+    /// 视频在当前地区不可用，需要 geo_bypass 或其他地区的代理
+    GeoBlocked(String),
+    TranscriptionFailed(String),
+    SummarizationFailed(String),
+    /// API 密钥无效或无权限
+    ApiAuthFailed(String),
+    /// 无法连接外部服务，或服务返回了认证以外的错误
+    Network(String),
+    /// 读写 vault 文件或目录失败
+    VaultIo(String),
+    /// 等待 vault 锁超时，另一个流水线或窗口正在写入
+    VaultBusy,
+    /// yt-dlp / whisper 运行超过时限，已被终止
+    Timeout(String),
+    Internal(String),
+}
+
+impl PipelineError {
+    fn kind(&self) -> &'static str {
+        match self {
+            PipelineError::ToolNotFound(_) => "tool_not_found",
+            PipelineError::InvalidInput(_) => "invalid_input",
+            PipelineError::NotFound(_) => "not_found",
+            PipelineError::AlreadyRunning(_) => "already_running",
+            PipelineError::Cancelled => "cancelled",
+            PipelineError::DownloadFailed(_) => "download_failed",
+            PipelineError::AgeRestricted(_) => "age_restricted",
+            PipelineError::GeoBlocked(_) => "geo_blocked",
+            PipelineError::TranscriptionFailed(_) => "transcription_failed",
+            PipelineError::SummarizationFailed(_) => "summarization_failed",
+            PipelineError::ApiAuthFailed(_) => "api_auth_failed",
+            PipelineError::Network(_) => "network",
+            PipelineError::VaultIo(_) => "vault_io",
+            PipelineError::VaultBusy => "vault_busy",
+            PipelineError::Timeout(_) => "timeout",
+            PipelineError::Internal(_) => "internal",
+        }
     }
-    
-    match fs::read_to_string(&config_path) {
-        Ok(content) => {
-            match toml::from_str::<Vault>(&content) {
-                Ok(vault) => Ok(vault),
-                Err(e) => Err(format!("解析vault配置失败: {}", e))
-            }
+}
+
+impl std::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::Cancelled => write!(f, "{}", PIPELINE_CANCELLED),
+            PipelineError::VaultBusy => write!(f, "vault 正忙，其他任务正在写入，请稍后重试"),
+            PipelineError::DownloadFailed(message) => write!(f, "下载失败: {}", message),
+            PipelineError::AgeRestricted(message) => write!(f, "视频有年龄限制，请提供已登录账号的 cookies（cookies_file 或 cookies_from_browser）: {}", message),
+            PipelineError::GeoBlocked(message) => write!(f, "视频在当前地区不可用，请开启 geo_bypass 或使用视频所在地区的代理: {}", message),
+            PipelineError::TranscriptionFailed(message) => write!(f, "转录失败: {}", message),
+            PipelineError::SummarizationFailed(message) => write!(f, "总结失败: {}", message),
+            PipelineError::ApiAuthFailed(message) => write!(f, "API认证失败: {}", message),
+            PipelineError::Network(message) => write!(f, "网络请求失败: {}", message),
+            PipelineError::Timeout(message) => write!(f, "运行超时: {}", message),
+            PipelineError::ToolNotFound(message)
+            | PipelineError::InvalidInput(message)
+            | PipelineError::NotFound(message)
+            | PipelineError::AlreadyRunning(message)
+            | PipelineError::VaultIo(message)
+            | PipelineError::Internal(message) => write!(f, "{}", message),
         }
-        Err(e) => Err(format!("读取vault配置失败: {}", e))
     }
 }
 
-fn save_vault(vault_path: &PathBuf, vault: &Vault) -> Result<(), String> {
-    fs::create_dir_all(vault_path)
-        .map_err(|e| format!("创建vault目录失败: {}", e))?;
-    
-    let config_path = get_vault_config_path(vault_path);
-    let content = toml::to_string_pretty(vault)
-        .map_err(|e| format!("序列化vault配置失败: {}", e))?;
-    
-    fs::write(&config_path, content)
-        .map_err(|e| format!("保存vault配置失败: {}", e))
+impl std::error::Error for PipelineError {}
+
+impl Serialize for PipelineError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("PipelineError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
 }
 
-fn get_current_timestamp() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    timestamp.to_string()
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String, PipelineError> {
+    serde_json::to_string(value)
+        .map_err(|e| PipelineError::Internal(format!("序列化结果失败: {}", e)))
 }
 
-#[tauri::command]
-async fn select_download_path() -> Result<String, String> {
-    // 使用系统的文件夹选择对话框
-    let result = rfd::AsyncFileDialog::new()
-        .set_title("选择下载文件夹")
-        .pick_folder()
-        .await;
-        
-    match result {
-        Some(folder) => Ok(folder.path().to_string_lossy().to_string()),
-        None => Err("未选择文件夹".to_string())
-    }
+/// 推送给前端的 `pipeline-progress` 事件负载
+#[derive(Serialize, Clone)]
+struct PipelineProgress {
+    video_id: String,
+    stage: String,
+    percent: Option<f32>,
+    message: String,
 }
 
-#[tauri::command]
-async fn process_video_pipeline(url: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>) -> Result<String, String> {
-    let base_dir = base_path.unwrap_or_else(|| std::env::temp_dir().to_string_lossy().to_string());
-    
-    // 展开波浪号路径 (~/Downloads -> /Users/username/Downloads)
-    let expanded_base_dir = expand_tilde_path(&base_dir);
-    
-    let vault_path = get_vault_path(&expanded_base_dir);
-    let video_id = generate_video_id(&url);
-    
-    // 加载vault
-    let mut vault = load_vault(&vault_path)?;
-    
-    let timestamp = get_current_timestamp();
-    
-    // 检查是否已有记录
-    let mut record = vault.videos.get(&video_id).cloned().unwrap_or_else(|| VideoRecord {
-        id: video_id.clone(),
-        url: url.clone(),
-        title: None,
-        downloaded: false,
-        transcribed: false,
-        summarized: false,
-        audio_file: None,
-        transcript_file: None,
-        transcript_content: None,
-        summary_content: None,
-        created_at: timestamp.clone(),
-        updated_at: timestamp.clone(),
-    });
-    
-    let video_dir = get_video_dir_path(&vault_path, &video_id);
-    fs::create_dir_all(&video_dir)
-        .map_err(|e| format!("创建视频目录失败: {}", e))?;
-    
-    let mut results = Vec::new();
-    
-    // 如果记录显示已下载但缺少 audio_file 路径，尝试找到文件
-    if record.downloaded && record.audio_file.is_none() {
-        if let Some(audio_file) = find_audio_file(&video_dir) {
-            record.audio_file = Some(audio_file);
-            record.updated_at = get_current_timestamp();
-            vault.videos.insert(video_id.clone(), record.clone());
-            save_vault(&vault_path, &vault)?;
-            results.push("✅ 找到已存在的音频文件".to_string());
+/// 转录过程中每识别出一个片段推送的 `transcript-chunk` 事件负载，如
+/// `{"video_id": "...", "start": 12.5, "end": 17.0, "text": "..."}`。
+/// 时间单位为秒，分段转录时已加上所在段的偏移；片段按时间顺序到达，
+/// 前端依次追加即可，转录完成后以记录中的 transcript_content 为准
+#[derive(Serialize, Clone)]
+struct TranscriptChunk {
+    video_id: String,
+    start: f64,
+    end: f64,
+    text: String,
+}
+
+struct ProgressEmitter {
+    app: AppHandle,
+    video_id: String,
+}
+
+impl ProgressEmitter {
+    fn emit(&self, stage: &str, percent: Option<f32>, message: &str) {
+        let payload = PipelineProgress {
+            video_id: self.video_id.clone(),
+            stage: stage.to_string(),
+            percent,
+            message: message.to_string(),
+        };
+        if let Err(e) = self.app.emit("pipeline-progress", payload) {
+            tracing::warn!("发送进度事件失败: {}", e);
         }
     }
     
-    // Step 1: 下载视频
-    if !record.downloaded {
-        results.push("正在下载视频...".to_string());
-        match download_video_to_dir(&url, &video_dir).await {
-            Ok((audio_file, title)) => {
-                record.downloaded = true;
-                record.audio_file = Some(audio_file.clone());
-                record.title = Some(title);
-                record.updated_at = get_current_timestamp();
-                
-                // 保存进度
-                vault.videos.insert(video_id.clone(), record.clone());
-                save_vault(&vault_path, &vault)?;
-                
-                results.push(format!("✅ 下载完成: {}", audio_file));
-            }
-            Err(e) => return Err(format!("下载失败: {}", e))
+    fn emit_transcript_chunk(&self, segment: &TranscriptSegment) {
+        let payload = TranscriptChunk {
+            video_id: self.video_id.clone(),
+            start: segment.start,
+            end: segment.end,
+            text: segment.text.trim().to_string(),
+        };
+        if let Err(e) = self.app.emit("transcript-chunk", payload) {
+            tracing::warn!("发送转录片段事件失败: {}", e);
         }
-    } else {
-        results.push("✅ 视频已下载，跳过下载步骤".to_string());
     }
-    
-    // Step 2: 转录音频
-    if !record.transcribed {
-        if let Some(audio_file) = &record.audio_file {
-            results.push("正在转录音频...".to_string());
-            match transcribe_audio_file(audio_file).await {
-                Ok(transcript_content) => {
-                    record.transcribed = true;
-                    record.transcript_content = Some(transcript_content.clone());
-                    record.updated_at = get_current_timestamp();
-                    
-                    // 保存进度
-                    vault.videos.insert(video_id.clone(), record.clone());
-                    save_vault(&vault_path, &vault)?;
-                    
-                    results.push("✅ 转录完成".to_string());
-                }
-                Err(e) => return Err(format!("转录失败: {}", e))
-            }
-        } else {
-            return Err("无法转录：未找到音频文件路径".to_string());
-        }
-    } else if record.transcribed {
-        results.push("✅ 音频已转录，跳过转录步骤".to_string());
+}
+
+const DEFAULT_WHISPER_MODEL: &str = "base";
+const WHISPER_MODELS: [&str; 7] = ["tiny", "base", "small", "medium", "large", "large-v2", "large-v3"];
+
+/// ISO 639-1 两字母语言代码
+const ISO_639_1_CODES: [&str; 184] = [
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az",
+    "ba", "be", "bg", "bh", "bi", "bm", "bn", "bo", "br", "bs",
+    "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy",
+    "da", "de", "dv", "dz",
+    "ee", "el", "en", "eo", "es", "et", "eu",
+    "fa", "ff", "fi", "fj", "fo", "fr", "fy",
+    "ga", "gd", "gl", "gn", "gu", "gv",
+    "ha", "he", "hi", "ho", "hr", "ht", "hu", "hy", "hz",
+    "ia", "id", "ie", "ig", "ii", "ik", "io", "is", "it", "iu",
+    "ja", "jv",
+    "ka", "kg", "ki", "kj", "kk", "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky",
+    "la", "lb", "lg", "li", "ln", "lo", "lt", "lu", "lv",
+    "mg", "mh", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my",
+    "na", "nb", "nd", "ne", "ng", "nl", "nn", "no", "nr", "nv", "ny",
+    "oc", "oj", "om", "or", "os",
+    "pa", "pi", "pl", "ps", "pt",
+    "qu",
+    "rm", "rn", "ro", "ru", "rw",
+    "sa", "sc", "sd", "se", "sg", "si", "sk", "sl", "sm", "sn", "so", "sq", "sr", "ss", "st", "su", "sv", "sw",
+    "ta", "te", "tg", "th", "ti", "tk", "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty",
+    "ug", "uk", "ur", "uz",
+    "ve", "vi", "vo",
+    "wa", "wo",
+    "xh",
+    "yi", "yo",
+    "za", "zh", "zu",
+];
+
+const SUBTITLE_FORMATS: [&str; 2] = ["srt", "vtt"];
+
+const WHISPER_TASK_TRANSCRIBE: &str = "transcribe";
+const WHISPER_TASK_TRANSLATE: &str = "translate";
+/// whisper `--task` 的取值，translate 把任何语言的语音译为英文
+const WHISPER_TASKS: [&str; 2] = [WHISPER_TASK_TRANSCRIBE, WHISPER_TASK_TRANSLATE];
+/// 英文译文所在的子目录，避免与原语言转录的同名输出文件冲突
+const TRANSLATION_DIR: &str = "translation";
+
+/// whisper `--device` 支持的计算设备
+const WHISPER_DEVICES: [&str; 3] = ["cpu", "cuda", "mps"];
+
+const PIPELINE_CANCELLED: &str = "流水线已取消";
+
+/// 单次流水线运行的取消信号，可在各阶段之间检查，也可用于中断正在运行的子进程
+#[derive(Clone)]
+struct CancelToken(Arc<tokio::sync::watch::Sender<bool>>);
+
+impl CancelToken {
+    fn new() -> Self {
+        CancelToken(Arc::new(tokio::sync::watch::channel(false).0))
     }
     
-    // Step 3: 生成总结
-    if !record.summarized && record.transcript_content.is_some() {
-        results.push("正在生成总结...".to_string());
-        let transcript = record.transcript_content.as_ref().unwrap();
-        let provider = match api_provider.as_deref() {
-            Some("deepseek") => ApiProvider::DeepSeek,
-            _ => ApiProvider::OpenAI,
-        };
-        match summarize_transcript_content(transcript, api_key, provider).await {
-            Ok(summary_content) => {
-                record.summarized = true;
-                record.summary_content = Some(summary_content);
-                record.updated_at = get_current_timestamp();
-                
-                // 保存最终进度
-                vault.videos.insert(video_id.clone(), record.clone());
-                save_vault(&vault_path, &vault)?;
-                
-                results.push("✅ 总结完成".to_string());
-            }
-            Err(e) => return Err(format!("总结失败: {}", e))
-        }
-    } else if record.summarized {
-        results.push("✅ 内容已总结，跳过总结步骤".to_string());
+    fn cancel(&self) {
+        self.0.send_replace(true);
     }
     
-    // 返回结果
-    let result_json = serde_json::to_string(&record)
-        .map_err(|e| format!("序列化结果失败: {}", e))?;
+    fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
     
-    Ok(result_json)
+    async fn cancelled(&self) {
+        let mut receiver = self.0.subscribe();
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
 }
 
-async fn download_video_to_dir(url: &str, output_dir: &PathBuf) -> Result<(String, String), String> {
-    // 先检查yt-dlp是否可用
-    let version_check = Command::new("yt-dlp")
-        .arg("--version")
-        .output();
-        
-    match version_check {
-        Err(_) => return Err("yt-dlp未安装或不在PATH中。请先安装yt-dlp: pip install yt-dlp".to_string()),
-        Ok(result) if !result.status.success() => {
-            return Err("yt-dlp无法正常运行，请检查安装".to_string());
-        }
-        _ => {}
-    }
-    
-    // 先获取视频信息（标题和可用性检查）
-    let info_output = Command::new("yt-dlp")
-        .arg("--print").arg("%(title)s")
-        .arg("--no-download")
-        .arg(url)
-        .output();
-        
-    let title = match info_output {
-        Ok(result) if result.status.success() => {
-            String::from_utf8_lossy(&result.stdout).trim().to_string()
-        }
-        Ok(result) => {
-            let stderr = String::from_utf8_lossy(&result.stderr);
-            return Err(format!("无法获取视频信息: {}", stderr));
-        }
-        Err(e) => return Err(format!("执行yt-dlp失败: {}", e))
-    };
-    
-    // 下载并转换为音频
-    let output = Command::new("yt-dlp")
-        .arg("--extract-audio")
-        .arg("--audio-format").arg("wav")
-        .arg("--audio-quality").arg("0")  // 最高质量
-        .arg("--output").arg(format!("{}/%(title)s.%(ext)s", output_dir.display()))
-        .arg("--verbose")  // 详细输出用于调试
-        .arg(url)
-        .output();
+/// 记录正在运行的流水线（按 video_id），由 Tauri 托管为全局状态
+#[derive(Default)]
+struct PipelineRegistry {
+    running: Mutex<HashMap<String, CancelToken>>,
+}
 
-    match output {
-        Ok(result) => {
-            let stdout = String::from_utf8_lossy(&result.stdout);
-            let stderr = String::from_utf8_lossy(&result.stderr);
-            
-            if result.status.success() {
-                // 等待一小段时间确保文件写入完成
-                tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-                
-                if let Some(audio_file) = find_audio_file(output_dir) {
-                    Ok((audio_file, title))
-                } else {
-                    // 如果找不到文件，提供详细的调试信息
-                    let dir_contents = list_directory_contents(output_dir);
-                    Err(format!(
-                        "下载似乎成功但未找到音频文件。\n目录: {}\n目录内容: {:?}\n\nyt-dlp输出:\nSTDOUT: {}\nSTDERR: {}", 
-                        output_dir.display(), 
-                        dir_contents,
-                        stdout.trim(),
-                        stderr.trim()
-                    ))
-                }
-            } else {
-                Err(format!("yt-dlp下载失败 (退出码: {})\nSTDOUT: {}\nSTDERR: {}", 
-                    result.status.code().unwrap_or(-1),
-                    stdout.trim(),
-                    stderr.trim()
-                ))
-            }
+impl PipelineRegistry {
+    fn start(&self, video_id: &str) -> Result<RunningPipeline<'_>, PipelineError> {
+        let mut running = self.running.lock().unwrap();
+        if running.contains_key(video_id) {
+            return Err(PipelineError::AlreadyRunning("该视频正在处理中".to_string()));
         }
-        Err(e) => Err(format!("执行 yt-dlp 失败: {}", e))
+        let token = CancelToken::new();
+        running.insert(video_id.to_string(), token.clone());
+        Ok(RunningPipeline {
+            registry: self,
+            video_id: video_id.to_string(),
+            token,
+        })
     }
 }
 
-fn list_directory_contents(dir: &PathBuf) -> Vec<String> {
-    if let Ok(entries) = fs::read_dir(dir) {
-        entries
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.file_name().to_string_lossy().to_string())
-            .collect()
-    } else {
-        vec!["无法读取目录".to_string()]
+/// 流水线结束（无论成功、失败还是取消）时自动从注册表中移除
+struct RunningPipeline<'a> {
+    registry: &'a PipelineRegistry,
+    video_id: String,
+    token: CancelToken,
+}
+
+impl Drop for RunningPipeline<'_> {
+    fn drop(&mut self) {
+        self.registry.running.lock().unwrap().remove(&self.video_id);
     }
 }
 
-async fn transcribe_audio_file(audio_file_path: &str) -> Result<String, String> {
-    // 使用 whisper 命令行工具进行转录
-    let output = Command::new("whisper")
-        .arg(audio_file_path)
-        .arg("--model").arg("base")  // 使用 base 模型，平衡速度和准确性
-        .arg("--output_format").arg("txt")  // 输出纯文本格式
-        .arg("--output_dir").arg(std::path::Path::new(audio_file_path).parent().unwrap())
-        .output();
+/// 默认同时运行的转录进程数，whisper 会占满 CPU/GPU，多个并行反而更慢
+const DEFAULT_TRANSCRIBE_CONCURRENCY: usize = 1;
+const MAX_TRANSCRIBE_CONCURRENCY: usize = 4;
 
-    match output {
-        Ok(result) => {
-            if result.status.success() {
-                // 查找生成的转录文本文件
-                if let Some(transcript_file) = find_transcript_file(audio_file_path) {
-                    match fs::read_to_string(&transcript_file) {
-                        Ok(content) => {
-                            // 清理文本内容，移除多余的空白字符
-                            let cleaned_content = content.trim().to_string();
-                            Ok(cleaned_content)
-                        }
-                        Err(e) => Err(format!("读取转录文件失败: {}", e))
+#[derive(Serialize, Clone, Copy)]
+struct QueueStatus {
+    limit: usize,
+    running: usize,
+    waiting: usize,
+}
+
+/// 所有流水线共享的转录队列，限制同时运行的 whisper 进程数；下载和总结不受限制
+struct TranscriptionQueue {
+    status: Mutex<QueueStatus>,
+    notify: tokio::sync::Notify,
+}
+
+impl TranscriptionQueue {
+    fn new(limit: usize) -> Self {
+        TranscriptionQueue {
+            status: Mutex::new(QueueStatus { limit, running: 0, waiting: 0 }),
+            notify: tokio::sync::Notify::new(),
+        }
+    }
+    
+    fn status(&self) -> QueueStatus {
+        *self.status.lock().unwrap()
+    }
+    
+    fn set_limit(&self, limit: usize) {
+        // 调小时已在运行的转录不受影响，只是空出的名额不再分配
+        self.status.lock().unwrap().limit = limit;
+        self.notify.notify_waiters();
+    }
+    
+    /// 等待一个转录名额；取消流水线时立即放弃排队
+    async fn acquire(&self, progress: &ProgressEmitter, cancel: &CancelToken) -> Result<TranscriptionSlot<'_>, PipelineError> {
+        let mut queued = false;
+        loop {
+            // 先登记唤醒再检查名额，避免检查后、等待前的释放被错过
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let mut status = self.status.lock().unwrap();
+                if status.running < status.limit {
+                    status.running += 1;
+                    if queued {
+                        status.waiting -= 1;
                     }
-                } else {
-                    Err("未找到转录输出文件".to_string())
+                    return Ok(TranscriptionSlot { queue: self });
+                }
+                if !queued {
+                    queued = true;
+                    status.waiting += 1;
+                    progress.emit("transcribe", None, &format!("等待其他视频转录完成（{} 个正在转录，{} 个在排队）", status.running, status.waiting));
+                }
+            }
+            tokio::select! {
+                _ = notified => {}
+                _ = cancel.cancelled() => {
+                    self.status.lock().unwrap().waiting -= 1;
+                    return Err(PipelineError::Cancelled);
                 }
-            } else {
-                let error = String::from_utf8_lossy(&result.stderr);
-                Err(format!("Whisper 转录失败: {}", error))
             }
         }
-        Err(e) => Err(format!("执行 Whisper 失败: {}. 请确保已安装 OpenAI Whisper", e))
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
+/// 转录结束（无论成功与否）时归还名额
+struct TranscriptionSlot<'a> {
+    queue: &'a TranscriptionQueue,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatCompletionRequest {
-    model: String,
-    messages: Vec<ChatMessage>,
-    max_tokens: u32,
-    temperature: f32,
+impl Drop for TranscriptionSlot<'_> {
+    fn drop(&mut self) {
+        self.queue.status.lock().unwrap().running -= 1;
+        self.queue.notify.notify_waiters();
+    }
 }
 
-#[derive(Clone)]
-enum ApiProvider {
-    OpenAI,
-    DeepSeek,
+/// 可以传单个字符串或字符串数组的参数
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum StringOrList {
+    One(String),
+    List(Vec<String>),
 }
 
-impl ApiProvider {
-    fn base_url(&self) -> &str {
-        match self {
-            ApiProvider::OpenAI => "https://api.openai.com/v1/chat/completions",
-            ApiProvider::DeepSeek => "https://api.deepseek.com/chat/completions",
-        }
+impl StringOrList {
+    /// 去掉空白项和重复项，保留原有顺序
+    fn to_vec(&self) -> Vec<String> {
+        let values = match self {
+            StringOrList::One(value) => std::slice::from_ref(value),
+            StringOrList::List(values) => values.as_slice(),
+        };
+        let mut seen = HashSet::new();
+        values.iter()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty() && seen.insert(value.clone()))
+            .collect()
+    }
+}
+
+/// 流水线的可选参数，前端以 `options` 对象传入，省略的字段使用默认值
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+struct PipelineOptions {
+    api_key: Option<String>,
+    api_provider: Option<String>,
+    whisper_model: Option<String>,
+    language: Option<String>,
+    subtitle_formats: Vec<String>,
+    /// 额外输出 whisper 的 JSON 结果并保存带时间戳的分段
+    include_segments: bool,
+    /// 片段 avg_logprob 的下限（如 -1.0），低于它的片段在转录文本中用 `[?]` 标出
+    min_confidence: Option<f64>,
+    /// 直接去掉低于 min_confidence 的片段，而不是标出
+    drop_low_confidence: bool,
+    /// 长音频按此分钟数切分后逐段转录，每段完成后保存进度，中断后可继续
+    transcribe_chunk_minutes: Option<u32>,
+    transcription_backend: Option<TranscriptionBackend>,
+    whisper_cpp_model: Option<String>,
+    download_max_attempts: Option<u32>,
+    /// 单次 yt-dlp 下载允许运行的分钟数
+    download_timeout_minutes: Option<u64>,
+    /// 单次 whisper 转录允许运行的分钟数，分段转录时按每段计算
+    transcribe_timeout_minutes: Option<u64>,
+    /// 把 whisper 逐行输出的转录文本整理成段落，默认开启；原始文本保留在转录文件中
+    format_paragraphs: Option<bool>,
+    audio_format: Option<String>,
+    audio_quality: Option<String>,
+    /// yt-dlp 的格式选择器（如 `bestaudio[ext=m4a]/bestaudio`），作为 `--format` 传入，音频仍按 audio_format 转换
+    format_selector: Option<String>,
+    /// 完整的总结系统提示词，提供时原样使用
+    summary_prompt: Option<String>,
+    /// 预设的总结风格，决定提示词和默认的 max_tokens；提供了 summary_prompt 时不生效
+    summary_style: Option<SummaryStyle>,
+    /// 总结输出语言（如 English），只替换默认提示词中的回复语言
+    /// 也可以是语言列表（如 ["中文", "English"]），每种语言单独生成一份总结，第一个为主语言
+    summary_language: Option<StringOrList>,
+    /// 每次总结请求允许生成的最大 token 数
+    max_tokens: Option<u32>,
+    /// 采样温度，0.0–2.0，越低输出越稳定
+    temperature: Option<f32>,
+    /// Netscape 格式的 cookies 文件，用于下载会员或年龄限制视频
+    cookies_file: Option<String>,
+    /// 从浏览器读取 cookies，如 chrome、firefox
+    cookies_from_browser: Option<String>,
+    /// 伪造 X-Forwarded-For 请求头绕过地区限制，对应 yt-dlp 的 `--geo-bypass`
+    geo_bypass: bool,
+    /// 替换 yt-dlp 默认的 User-Agent，用于会识别客户端的网站
+    user_agent: Option<String>,
+    /// 额外的 HTTP 请求头，每项为 `[名称, 值]`，对应 yt-dlp 的 `--add-header`
+    headers: Vec<(String, String)>,
+    /// 跳过不适合该年龄观看的视频，对应 yt-dlp 的 `--age-limit`
+    age_limit: Option<u32>,
+    /// 额外保存 yt-dlp 的完整 JSON 元数据
+    save_metadata: bool,
+    /// 下载带画面的视频文件并保留，音频仍从中提取用于转录；视频文件通常比音频大得多
+    keep_video: bool,
+    /// 只下载视频中的一段，格式为 `HH:MM:SS`、`MM:SS` 或秒数，需要同时指定结束时间
+    start_time: Option<String>,
+    end_time: Option<String>,
+    /// 下载限速，如 `500K`、`1.5M`，对应 yt-dlp 的 `--limit-rate`
+    rate_limit: Option<String>,
+    /// 每次下载前等待的秒数，避免频繁请求触发站点的反爬限制
+    sleep_interval: Option<u32>,
+    /// 指定后在 sleep_interval 到该值之间随机等待
+    max_sleep_interval: Option<u32>,
+    /// yt-dlp 可执行文件路径，未指定时使用 PATH 中的 yt-dlp
+    yt_dlp_path: Option<String>,
+    /// 转录程序的可执行文件路径，如虚拟环境中的 whisper，未指定时在 PATH 中查找
+    whisper_path: Option<String>,
+    /// yt-dlp 和总结 API 请求使用的代理，如 http://127.0.0.1:7890 或 socks5://127.0.0.1:1080
+    proxy: Option<String>,
+    /// api_provider 为 custom 时使用的 OpenAI 兼容接口地址，如 http://localhost:11434/v1
+    custom_base_url: Option<String>,
+    /// api_provider 为 custom 时使用的模型名称
+    custom_model: Option<String>,
+    /// 总结使用的模型，如 gpt-4o，未指定时使用所选服务的默认模型
+    summary_model: Option<String>,
+    /// 离线模式：只用本地的简单总结，不发出任何网络请求
+    offline: bool,
+    /// 转录完成后是否保留音频文件，默认保留
+    keep_audio: Option<bool>,
+    /// whisper 使用的计算设备：cpu、cuda 或 mps，未指定时由 whisper 自行选择
+    device: Option<String>,
+    /// 是否以 FP16 精度推理，CPU 上不支持 FP16，whisper 会自动回退到 FP32
+    fp16: Option<bool>,
+    /// 用 whisperx 识别说话人，whisperx 或 Hugging Face 令牌不可用时退回普通转录
+    diarize: bool,
+    /// 未指定时使用 vault 配置或环境变量 HF_TOKEN
+    hf_token: Option<String>,
+    /// whisper 的任务：transcribe（默认）或 translate。translate 时在原语言转录之外再译出一份英文
+    task: Option<String>,
+    /// process_batch 同时处理的链接数，默认逐个处理
+    batch_concurrency: Option<usize>,
+    /// 流水线结束（成功或失败）后向该地址 POST 一条 JSON 通知，可用于 Discord / Slack 的 incoming webhook
+    webhook_url: Option<String>,
+    /// 由 retranscribe_video 设置：忽略已有转录结果，从转录步骤重新开始
+    #[serde(skip)]
+    force_retranscribe: bool,
+    /// 由 redownload_video 设置：删除已下载的音频，从下载步骤重新开始
+    #[serde(skip)]
+    force_redownload: bool,
+}
+
+impl PipelineOptions {
+    /// 调用方未指定的参数使用 vault 配置中保存的默认值
+    fn with_defaults(mut self, config: &VaultConfig) -> Self {
+        self.api_provider = self.api_provider.or_else(|| config.api_provider.clone());
+        self.whisper_model = self.whisper_model.or_else(|| config.whisper_model.clone());
+        self.audio_format = self.audio_format.or_else(|| config.audio_format.clone());
+        self.language = self.language.or_else(|| config.language.clone());
+        self.proxy = self.proxy.or_else(|| config.proxy.clone());
+        self.yt_dlp_path = self.yt_dlp_path.or_else(|| config.yt_dlp_path.clone());
+        self.whisper_path = self.whisper_path.or_else(|| config.whisper_path.clone());
+        self.max_tokens = self.max_tokens.or(config.max_tokens);
+        self.temperature = self.temperature.or(config.temperature);
+        self.rate_limit = self.rate_limit.or_else(|| config.rate_limit.clone());
+        self.sleep_interval = self.sleep_interval.or(config.sleep_interval);
+        self.max_sleep_interval = self.max_sleep_interval.or(config.max_sleep_interval);
+        self.hf_token = self.hf_token.or_else(|| config.hf_token.clone());
+        self.device = self.device.or_else(|| config.device.clone());
+        self.fp16 = self.fp16.or(config.fp16);
+        self.webhook_url = self.webhook_url.or_else(|| config.webhook_url.clone());
+        self
     }
     
-    fn default_model(&self) -> &str {
-        match self {
-            ApiProvider::OpenAI => "gpt-3.5-turbo",
-            ApiProvider::DeepSeek => "deepseek-chat",
+    /// 命令顶层的 api_key / api_provider 参数为兼容旧版前端而保留，优先级高于 options 中的同名字段
+    fn with_api(mut self, api_key: Option<String>, api_provider: Option<String>) -> Self {
+        if api_key.is_some() {
+            self.api_key = api_key;
+        }
+        if api_provider.is_some() {
+            self.api_provider = api_provider;
         }
+        self
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatChoice {
-    message: ChatMessage,
+const DEFAULT_DOWNLOAD_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_DOWNLOAD_TIMEOUT_MINUTES: u64 = 60;
+/// 在 CPU 上用较大模型转录长视频可能需要很久，默认值留足余量
+const DEFAULT_TRANSCRIBE_TIMEOUT_MINUTES: u64 = 120;
+
+/// stderr 中出现这些内容说明是临时性网络问题，值得重试
+const TRANSIENT_DOWNLOAD_ERRORS: [&str; 5] = ["HTTP Error 5", "Temporary failure", "Connection reset", "Connection aborted", "timed out"];
+/// 这些错误重试也不会成功，应立即失败
+const PERMANENT_DOWNLOAD_ERRORS: [&str; 4] = ["Video unavailable", "Private video", "This video has been removed", "Unsupported URL"];
+/// 年龄限制和地区限制的 stderr 特征，用于提示对应的解决办法
+const AGE_RESTRICTED_ERRORS: [&str; 4] = ["Sign in to confirm your age", "age-restricted", "age restricted", "inappropriate for some users"];
+const GEO_BLOCKED_ERRORS: [&str; 6] = ["not available in your country", "not made this video available in your country", "not available from your location", "geo restriction", "geo-restricted", "blocked it in your country"];
+
+const DEFAULT_AUDIO_FORMAT: &str = "wav";
+/// yt-dlp `--audio-format` 支持的格式
+const AUDIO_FORMATS: [&str; 7] = ["mp3", "m4a", "aac", "flac", "opus", "wav", "vorbis"];
+/// 最高质量（VBR 0）
+const DEFAULT_AUDIO_QUALITY: &str = "0";
+
+/// 传给 yt-dlp 的下载参数
+#[derive(Clone)]
+struct DownloadOptions {
+    /// 包括首次下载在内的最大尝试次数
+    max_attempts: u32,
+    /// 每次尝试的时限
+    timeout: std::time::Duration,
+    audio_format: String,
+    /// `--audio-quality` 的值：0（最好）到 10（最差）的 VBR 等级，或 `128K` 这样的比特率
+    audio_quality: String,
+    cookies_file: Option<String>,
+    cookies_from_browser: Option<String>,
+    proxy: Option<String>,
+    /// yt-dlp 可执行文件，默认为 PATH 中的 yt-dlp
+    yt_dlp: String,
+    save_metadata: bool,
+    keep_video: bool,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    rate_limit: Option<String>,
+    sleep_interval: Option<u32>,
+    max_sleep_interval: Option<u32>,
+    geo_bypass: bool,
+    age_limit: Option<u32>,
+    format_selector: Option<String>,
+    user_agent: Option<String>,
+    headers: Vec<(String, String)>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatCompletionResponse {
-    choices: Vec<ChatChoice>,
+/// 视频中的一段时间，单位为秒
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+struct TimeRange {
+    start: u64,
+    end: u64,
 }
 
-async fn summarize_transcript_content(transcript: &str, api_key: Option<String>, provider: ApiProvider) -> Result<String, String> {
-    // 如果没有提供API密钥，使用本地LLM或返回简单总结
-    if api_key.is_none() {
-        return Ok(generate_simple_summary(&transcript));
+impl TimeRange {
+    /// yt-dlp `--download-sections` 的参数，`*` 表示按时间而不是章节名截取
+    fn download_section(&self) -> String {
+        format!("*{}-{}", format_clock_time(self.start), format_clock_time(self.end))
     }
+}
+
+fn format_clock_time(total_seconds: u64) -> String {
+    format!("{:02}:{:02}:{:02}", total_seconds / 3600, total_seconds % 3600 / 60, total_seconds % 60)
+}
+
+/// 解析 `HH:MM:SS`、`MM:SS` 或纯秒数，除第一段外分钟和秒都必须小于 60
+fn parse_clock_time(value: &str) -> Option<u64> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() > 3 || parts.iter().any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let numbers: Vec<u64> = parts.iter().map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    if numbers[1..].iter().any(|&number| number >= 60) {
+        return None;
+    }
+    Some(numbers.iter().fold(0, |total, &number| total * 60 + number))
+}
+
+const YT_DLP_BINARY: &str = "yt-dlp";
+
+/// 检查用户指定的程序路径存在且可执行
+fn validate_executable(path: &str, tool: &str) -> Result<(), PipelineError> {
+    let metadata = fs::metadata(path)
+        .map_err(|_| PipelineError::ToolNotFound(format!("指定的 {} 路径不存在: {}", tool, path)))?;
+    
+    #[cfg(unix)]
+    let executable = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    };
+    #[cfg(not(unix))]
+    let executable = metadata.is_file();
+    
+    if executable {
+        Ok(())
+    } else {
+        Err(PipelineError::ToolNotFound(format!("指定的 {} 路径不是可执行文件: {}", tool, path)))
+    }
+}
+
+/// yt-dlp 和 reqwest 都支持的代理协议
+const PROXY_SCHEMES: [&str; 6] = ["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+fn validate_proxy_url(proxy: &str) -> Result<(), PipelineError> {
+    let parsed = reqwest::Url::parse(proxy)
+        .map_err(|e| PipelineError::InvalidInput(format!("无效的代理地址 {}: {}", proxy, e)))?;
+    if !PROXY_SCHEMES.contains(&parsed.scheme()) || parsed.host_str().is_none() {
+        return Err(PipelineError::InvalidInput(format!("无效的代理地址: {}，请使用如 http://127.0.0.1:7890 或 socks5://127.0.0.1:1080 的格式", proxy)));
+    }
+    Ok(())
+}
+
+/// 请求头名称只能包含 HTTP token 字符，值不能包含换行等控制字符，避免拼出多余的请求头
+fn validate_header(name: &str, value: &str) -> Result<(), PipelineError> {
+    let valid_name = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+    if !valid_name {
+        return Err(PipelineError::InvalidInput(format!("无效的请求头名称: {}，只能包含字母、数字和 -_ 等符号", name)));
+    }
+    if value.chars().any(char::is_control) {
+        return Err(PipelineError::InvalidInput(format!("请求头 {} 的值不能包含换行等控制字符", name)));
+    }
+    Ok(())
+}
+
+fn validate_webhook_url(webhook_url: &str) -> Result<(), PipelineError> {
+    let parsed = reqwest::Url::parse(webhook_url)
+        .map_err(|e| PipelineError::InvalidInput(format!("无效的 webhook 地址 {}: {}", webhook_url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(PipelineError::InvalidInput(format!("无效的 webhook 地址: {}，只支持 http 或 https", webhook_url)));
+    }
+    Ok(())
+}
+
+impl DownloadOptions {
+    fn from_pipeline_options(options: &PipelineOptions) -> Self {
+        DownloadOptions {
+            max_attempts: options.download_max_attempts.unwrap_or(DEFAULT_DOWNLOAD_MAX_ATTEMPTS).max(1),
+            timeout: std::time::Duration::from_secs(options.download_timeout_minutes.unwrap_or(DEFAULT_DOWNLOAD_TIMEOUT_MINUTES).max(1) * 60),
+            audio_format: options.audio_format.as_ref()
+                .map(|format| format.trim().to_lowercase())
+                .unwrap_or_else(|| DEFAULT_AUDIO_FORMAT.to_string()),
+            audio_quality: options.audio_quality.as_ref()
+                .map(|quality| quality.trim().to_string())
+                .unwrap_or_else(|| DEFAULT_AUDIO_QUALITY.to_string()),
+            cookies_file: options.cookies_file.as_ref()
+                .map(|path| expand_tilde_path(path.trim())),
+            cookies_from_browser: options.cookies_from_browser.as_ref()
+                .map(|browser| browser.trim().to_lowercase()),
+            proxy: options.proxy.as_ref()
+                .map(|proxy| proxy.trim().to_string())
+                .filter(|proxy| !proxy.is_empty()),
+            yt_dlp: options.yt_dlp_path.as_deref()
+                .map(|path| expand_tilde_path(path.trim()))
+                .filter(|path| !path.is_empty())
+                .unwrap_or_else(|| YT_DLP_BINARY.to_string()),
+            save_metadata: options.save_metadata,
+            keep_video: options.keep_video,
+            start_time: options.start_time.as_ref().map(|time| time.trim().to_string()),
+            end_time: options.end_time.as_ref().map(|time| time.trim().to_string()),
+            rate_limit: options.rate_limit.as_ref()
+                .map(|rate| rate.trim().to_string())
+                .filter(|rate| !rate.is_empty()),
+            sleep_interval: options.sleep_interval,
+            max_sleep_interval: options.max_sleep_interval,
+            geo_bypass: options.geo_bypass,
+            age_limit: options.age_limit,
+            format_selector: options.format_selector.as_ref().map(|selector| selector.trim().to_string()),
+            user_agent: options.user_agent.as_ref().map(|agent| agent.trim().to_string()),
+            headers: options.headers.iter()
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect(),
+        }
+    }
+    
+    fn validate(&self) -> Result<(), PipelineError> {
+        if !AUDIO_FORMATS.contains(&self.audio_format.as_str()) {
+            return Err(PipelineError::InvalidInput(format!("不支持的音频格式: {}，可选值: {}", self.audio_format, AUDIO_FORMATS.join(", "))));
+        }
+        
+        let is_vbr_level = self.audio_quality.parse::<u8>().is_ok_and(|level| level <= 10);
+        let is_bitrate = self.audio_quality.strip_suffix(['K', 'k'])
+            .is_some_and(|rate| !rate.is_empty() && rate.chars().all(|c| c.is_ascii_digit()));
+        if !is_vbr_level && !is_bitrate {
+            return Err(PipelineError::InvalidInput(format!("无效的音频质量: {}，请使用 0-10 或如 128K 的比特率", self.audio_quality)));
+        }
+        
+        if let Some(proxy) = &self.proxy {
+            validate_proxy_url(proxy)?;
+        }
+        
+        if self.yt_dlp != YT_DLP_BINARY {
+            validate_executable(&self.yt_dlp, "yt-dlp")?;
+        }
+        
+        if let Some(cookies_file) = &self.cookies_file {
+            if !Path::new(cookies_file).is_file() {
+                return Err(PipelineError::InvalidInput(format!("cookies 文件不存在: {}，请检查路径或重新从浏览器导出", cookies_file)));
+            }
+        }
+        
+        self.time_range()?;
+        
+        if let Some(user_agent) = &self.user_agent {
+            if user_agent.is_empty() || user_agent.chars().any(char::is_control) {
+                return Err(PipelineError::InvalidInput("无效的 User-Agent：不能为空或包含换行等控制字符".to_string()));
+            }
+        }
+        for (name, value) in &self.headers {
+            validate_header(name, value)?;
+        }
+        
+        if self.format_selector.as_deref().is_some_and(str::is_empty) {
+            return Err(PipelineError::InvalidInput("format_selector 不能为空，不需要时请不要传入".to_string()));
+        }
+        
+        if let Some(rate) = &self.rate_limit {
+            let number = rate.strip_suffix(['K', 'k', 'M', 'm', 'G', 'g']).unwrap_or(rate);
+            if number.parse::<f64>().map_or(true, |value| !value.is_finite() || value <= 0.0) {
+                return Err(PipelineError::InvalidInput(format!("无效的下载限速: {}，请使用如 500K 或 1.5M 的格式", rate)));
+            }
+        }
+        
+        match (self.sleep_interval, self.max_sleep_interval) {
+            (None, Some(_)) => return Err(PipelineError::InvalidInput("指定 max_sleep_interval 时需要同时指定 sleep_interval".to_string())),
+            (Some(min), Some(max)) if max < min => {
+                return Err(PipelineError::InvalidInput(format!("max_sleep_interval ({}) 不能小于 sleep_interval ({})", max, min)));
+            }
+            _ => {}
+        }
+        
+        Ok(())
+    }
+    
+    fn time_range(&self) -> Result<Option<TimeRange>, PipelineError> {
+        let parse = |value: &str| parse_clock_time(value)
+            .ok_or_else(|| PipelineError::InvalidInput(format!("无效的时间: {}，请使用 HH:MM:SS、MM:SS 或秒数", value)));
+        match (self.start_time.as_deref(), self.end_time.as_deref()) {
+            (None, None) => Ok(None),
+            (Some(start), Some(end)) => {
+                let range = TimeRange { start: parse(start)?, end: parse(end)? };
+                if range.start >= range.end {
+                    return Err(PipelineError::InvalidInput(format!("开始时间 {} 必须早于结束时间 {}", start, end)));
+                }
+                Ok(Some(range))
+            }
+            _ => Err(PipelineError::InvalidInput("截取片段需要同时指定开始时间和结束时间".to_string())),
+        }
+    }
+    
+    /// 格式选择器与 `--extract-audio` 或 keep_video 冲突时的提示：
+    /// 只选画面的格式没有音轨可提取，keep_video 自带的画质选择会被替换
+    fn format_selector_warning(&self) -> Option<String> {
+        let selector = self.format_selector.as_deref()?;
+        let video_only = selector.split('/').any(|format| {
+            let format = format.trim();
+            (format.starts_with("bestvideo") || format.starts_with("worstvideo")) && !format.contains('+')
+        });
+        if video_only {
+            Some(format!("格式选择器 {} 可能只选中无音轨的视频流，提取音频会失败，建议使用如 bestvideo+bestaudio 的组合", selector))
+        } else if self.keep_video {
+            Some(format!("格式选择器 {} 会替换 keep_video 默认的画质选择，保留的视频以选择器为准", selector))
+        } else {
+            None
+        }
+    }
+    
+    fn uses_custom_headers(&self) -> bool {
+        self.user_agent.is_some() || !self.headers.is_empty()
+    }
+    
+    fn uses_cookies(&self) -> bool {
+        self.cookies_file.is_some() || self.cookies_from_browser.is_some()
+    }
+    
+    /// 输出统一为 UTF-8，避免 Windows 上 cp932 / gbk 等控制台编码把非 ASCII 标题弄乱
+    fn yt_dlp_command(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.yt_dlp);
+        command.arg("--encoding").arg("utf-8");
+        force_utf8_output(&mut command);
+        command
+    }
+    
+    /// 追加 yt-dlp 的 cookies 和代理参数，查询信息和下载时都需要
+    fn apply_network_args(&self, command: &mut tokio::process::Command) {
+        if let Some(proxy) = &self.proxy {
+            command.arg("--proxy").arg(proxy);
+        }
+        if let Some(cookies_file) = &self.cookies_file {
+            command.arg("--cookies").arg(cookies_file);
+        }
+        if let Some(browser) = &self.cookies_from_browser {
+            command.arg("--cookies-from-browser").arg(browser);
+        }
+        if self.geo_bypass {
+            command.arg("--geo-bypass");
+        }
+        if let Some(user_agent) = &self.user_agent {
+            command.arg("--user-agent").arg(user_agent);
+        }
+        for (name, value) in &self.headers {
+            command.arg("--add-header").arg(format!("{}: {}", name, value));
+        }
+    }
+}
+
+/// 让 Python 程序（yt-dlp、whisper、whisperx）的标准输出和写出的文件都使用 UTF-8，不受系统区域设置影响
+fn force_utf8_output(command: &mut tokio::process::Command) {
+    command
+        .env("PYTHONIOENCODING", "utf-8")
+        .env("PYTHONUTF8", "1");
+}
+
+/// 按 UTF-8 解码外部工具的输出或写出的文件，去掉开头的 BOM；
+/// 仍有无效字节时替换为 U+FFFD 并记录日志，而不是让整个步骤失败
+fn decode_tool_output(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(e) => {
+            tracing::warn!("输出不是有效的 UTF-8，无效字节已被替换: {}", e);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    }
+}
+
+fn read_text_file(path: &str) -> std::io::Result<String> {
+    fs::read(path).map(|bytes| decode_tool_output(&bytes))
+}
+
+/// 音频格式对应的文件扩展名
+fn audio_format_extension(format: &str) -> &str {
+    match format {
+        "vorbis" => "ogg",
+        other => other,
+    }
+}
+
+/// 传给 whisper 的转录参数
+#[derive(Clone)]
+struct TranscribeOptions {
+    model: String,
+    language: Option<String>,
+    subtitle_formats: Vec<String>,
+    include_segments: bool,
+    min_confidence: Option<f64>,
+    drop_low_confidence: bool,
+    format_paragraphs: bool,
+    /// 分段转录时每段的秒数
+    chunk_seconds: Option<u64>,
+    /// 未指定时根据 PATH 中可用的程序自动选择
+    backend: Option<TranscriptionBackend>,
+    /// whisper.cpp 需要的 ggml 模型文件路径
+    whisper_cpp_model: Option<String>,
+    /// 用户指定的转录程序路径，代替 PATH 查找
+    whisper_path: Option<String>,
+    timeout: std::time::Duration,
+    diarize: bool,
+    hf_token: Option<String>,
+    device: Option<String>,
+    fp16: Option<bool>,
+    /// transcribe 或 translate
+    task: String,
+    /// whisper 输出文件的目录，未指定时与音频文件相同。流水线总是指定为视频在 vault 中的目录，
+    /// 音频不在 vault 中或位于只读位置时转录结果也写入 vault
+    output_dir: Option<PathBuf>,
+}
+
+/// 转录所用的命令行工具
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum TranscriptionBackend {
+    /// OpenAI 官方的 Python 版 `whisper`
+    PythonWhisper,
+    /// whisper.cpp 的 `whisper-cli`（旧版本为 `main`）
+    WhisperCpp,
+}
+
+const PYTHON_WHISPER_BINARY: &str = "whisper";
+/// 支持说话人识别的 whisperx，命令行参数与 Python 版 whisper 兼容
+const WHISPERX_BINARY: &str = "whisperx";
+const HF_TOKEN_ENV: &str = "HF_TOKEN";
+const WHISPER_CPP_BINARIES: [&str; 3] = ["whisper-cli", "whisper-cpp", "main"];
+
+impl TranscriptionBackend {
+    /// 确定实际使用的后端和可执行文件名
+    fn resolve(preferred: Option<TranscriptionBackend>) -> (TranscriptionBackend, String) {
+        let whisper_cpp_binary = || WHISPER_CPP_BINARIES.iter().find(|name| find_in_path(name).is_some());
+        
+        match preferred {
+            Some(TranscriptionBackend::PythonWhisper) => (TranscriptionBackend::PythonWhisper, PYTHON_WHISPER_BINARY.to_string()),
+            Some(TranscriptionBackend::WhisperCpp) => {
+                let binary = whisper_cpp_binary().unwrap_or(&WHISPER_CPP_BINARIES[0]);
+                (TranscriptionBackend::WhisperCpp, binary.to_string())
+            }
+            None => {
+                if find_in_path(PYTHON_WHISPER_BINARY).is_some() {
+                    return (TranscriptionBackend::PythonWhisper, PYTHON_WHISPER_BINARY.to_string());
+                }
+                match whisper_cpp_binary() {
+                    Some(binary) => (TranscriptionBackend::WhisperCpp, binary.to_string()),
+                    None => (TranscriptionBackend::PythonWhisper, PYTHON_WHISPER_BINARY.to_string()),
+                }
+            }
+        }
+    }
+    
+    fn install_hint(&self) -> &str {
+        match self {
+            TranscriptionBackend::PythonWhisper => "请确保已安装 OpenAI Whisper: pip install openai-whisper",
+            TranscriptionBackend::WhisperCpp => "请确保已安装 whisper.cpp 并且 whisper-cli 在 PATH 中",
+        }
+    }
+}
+
+/// 在 PATH 中查找可执行文件
+fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| {
+        let candidate = dir.join(binary);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let candidate = dir.join(format!("{}.exe", binary));
+        candidate.is_file().then_some(candidate)
+    })
+}
+
+/// 一次转录的产物：纯文本内容以及各输出文件路径
+struct TranscriptionOutput {
+    content: String,
+    transcript_file: String,
+    subtitle_files: HashMap<String, String>,
+    segments: Option<Vec<TranscriptSegment>>,
+    /// whisper 自动检测到的语言，只有 json 输出中才有
+    detected_language: Option<String>,
+    /// 不影响转录结果的问题，如跳过了说话人识别
+    warnings: Vec<String>,
+}
+
+impl TranscribeOptions {
+    fn from_pipeline_options(options: &PipelineOptions) -> Self {
+        TranscribeOptions {
+            model: options.whisper_model.clone().unwrap_or_else(|| DEFAULT_WHISPER_MODEL.to_string()),
+            language: options.language.as_ref().map(|code| code.trim().to_lowercase()),
+            subtitle_formats: options.subtitle_formats.iter().map(|format| format.trim().to_lowercase()).collect(),
+            include_segments: options.include_segments,
+            min_confidence: options.min_confidence,
+            drop_low_confidence: options.drop_low_confidence,
+            format_paragraphs: options.format_paragraphs.unwrap_or(true),
+            chunk_seconds: options.transcribe_chunk_minutes.map(|minutes| u64::from(minutes) * 60),
+            backend: options.transcription_backend,
+            whisper_cpp_model: options.whisper_cpp_model.as_ref().map(|path| expand_tilde_path(path)),
+            whisper_path: options.whisper_path.as_deref()
+                .map(|path| expand_tilde_path(path.trim()))
+                .filter(|path| !path.is_empty()),
+            timeout: std::time::Duration::from_secs(options.transcribe_timeout_minutes.unwrap_or(DEFAULT_TRANSCRIBE_TIMEOUT_MINUTES).max(1) * 60),
+            diarize: options.diarize,
+            hf_token: options.hf_token.clone()
+                .or_else(|| std::env::var(HF_TOKEN_ENV).ok())
+                .map(|token| token.trim().to_string())
+                .filter(|token| !token.is_empty()),
+            device: options.device.as_ref()
+                .map(|device| device.trim().to_lowercase())
+                .filter(|device| !device.is_empty()),
+            fp16: options.fp16,
+            task: options.task.as_ref()
+                .map(|task| task.trim().to_lowercase())
+                .unwrap_or_else(|| WHISPER_TASK_TRANSCRIBE.to_string()),
+            output_dir: None,
+        }
+    }
+    
+    fn output_dir(&self, audio_file_path: &str) -> PathBuf {
+        self.output_dir.clone()
+            .unwrap_or_else(|| Path::new(audio_file_path).parent().unwrap().to_path_buf())
+    }
+    
+    /// 分段或置信度都只能从 json 输出中得到
+    fn needs_json(&self) -> bool {
+        self.include_segments || self.min_confidence.is_some()
+    }
+    
+    /// 输出目录中与音频同名、扩展名为 format 的文件，即 whisper 默认的输出文件名
+    fn output_file(&self, audio_file_path: &str, format: &str) -> PathBuf {
+        let stem = Path::new(audio_file_path).file_stem().unwrap_or_default().to_string_lossy();
+        self.output_dir(audio_file_path).join(format!("{}.{}", stem, format))
+    }
+    
+    fn validate(&self) -> Result<(), PipelineError> {
+        validate_whisper_model(&self.model)?;
+        if !WHISPER_TASKS.contains(&self.task.as_str()) {
+            return Err(PipelineError::InvalidInput(format!("不支持的转录任务: {}，可选值: {}", self.task, WHISPER_TASKS.join(", "))));
+        }
+        if let Some(whisper_path) = &self.whisper_path {
+            validate_executable(whisper_path, "whisper")?;
+        }
+        if self.chunk_seconds == Some(0) {
+            return Err(PipelineError::InvalidInput("分段转录的时长必须大于 0 分钟".to_string()));
+        }
+        if let Some(threshold) = self.min_confidence {
+            if !threshold.is_finite() || threshold > 0.0 {
+                return Err(PipelineError::InvalidInput(format!("无效的置信度下限: {}，avg_logprob 不大于 0，常用 -1.0", threshold)));
+            }
+        }
+        if let Some(language) = &self.language {
+            validate_language_code(language)?;
+        }
+        if let Some(device) = &self.device {
+            if !WHISPER_DEVICES.contains(&device.as_str()) {
+                return Err(PipelineError::InvalidInput(format!("不支持的计算设备: {}，可选值: {}", device, WHISPER_DEVICES.join(", "))));
+            }
+        }
+        for format in &self.subtitle_formats {
+            if !SUBTITLE_FORMATS.contains(&format.as_str()) {
+                return Err(PipelineError::InvalidInput(format!("不支持的字幕格式: {}，可选值: {}", format, SUBTITLE_FORMATS.join(", "))));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn greet(name: &str) -> String {
+    format!("Hello, {}! You've been greeted from Rust!", name)
+}
+
+#[derive(Serialize)]
+struct ToolStatus {
+    available: bool,
+    version: Option<String>,
+}
+
+impl ToolStatus {
+    async fn probe(binary: &str, version_flag: &str) -> Self {
+        match probe_tool_version(binary, version_flag).await {
+            Ok(version) => ToolStatus { available: true, version },
+            Err(_) => ToolStatus { available: false, version: None },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiKeyStatus {
+    provided: bool,
+    /// 仅按长度和前缀粗略判断，不发起网络请求
+    looks_valid: bool,
+}
+
+#[derive(Serialize)]
+struct EnvironmentReport {
+    yt_dlp: ToolStatus,
+    whisper: ToolStatus,
+    whisper_cpp: ToolStatus,
+    ffmpeg: ToolStatus,
+    api_key: ApiKeyStatus,
+}
+
+/// 检查外部工具是否可用及其版本，供前端显示安装引导
+#[tauri::command]
+async fn check_environment(api_key: Option<String>, api_provider: Option<String>, base_path: Option<String>) -> Result<String, PipelineError> {
+    let whisper_cpp_binary = WHISPER_CPP_BINARIES.iter()
+        .find(|name| find_in_path(name).is_some())
+        .unwrap_or(&WHISPER_CPP_BINARIES[0]);
+    
+    // 检查 vault 配置中指定的程序路径，未配置时使用 PATH 中的默认名称
+    let config = load_vault_config(&resolve_vault_path(base_path))?;
+    let yt_dlp = config.yt_dlp_path.as_deref().map(expand_tilde_path).unwrap_or_else(|| YT_DLP_BINARY.to_string());
+    let whisper = config.whisper_path.as_deref().map(expand_tilde_path).unwrap_or_else(|| PYTHON_WHISPER_BINARY.to_string());
     
-    let api_key = api_key.unwrap();
-    let client = reqwest::Client::new();
-    
-    let messages = vec![
-        ChatMessage {
-            role: "system".to_string(),
-            content: "你是一个专业的内容总结助手。请为用户提供简洁、准确的视频内容总结。总结应该包含主要观点、重要信息和关键结论。请用中文回复。".to_string(),
+    let api_key = api_key.map(|key| key.trim().to_string()).filter(|key| !key.is_empty());
+    let report = EnvironmentReport {
+        yt_dlp: ToolStatus::probe(&yt_dlp, "--version").await,
+        whisper: ToolStatus::probe(&whisper, "--version").await,
+        whisper_cpp: ToolStatus::probe(whisper_cpp_binary, "--version").await,
+        ffmpeg: ToolStatus::probe(FFMPEG_BINARY, "-version").await,
+        api_key: ApiKeyStatus {
+            provided: api_key.is_some(),
+            looks_valid: api_key.as_deref().is_some_and(|key| api_key_looks_valid(key, api_provider.as_deref())),
         },
-        ChatMessage {
-            role: "user".to_string(),
-            content: format!("请总结以下视频转录内容，提取主要观点和重要信息：\n\n{}", transcript),
+    };
+    
+    to_json(&report)
+}
+
+/// 检测工具版本的时限，卡住的程序不应让环境检查或流水线一直等待
+const TOOL_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// 运行 `<binary> --version`（ffmpeg 为 `-version`）：程序无法启动时返回 ToolNotFound，
+/// 超时返回 Timeout，能启动但执行失败时返回 Ok(None)
+async fn probe_tool_version(binary: &str, version_flag: &str) -> Result<Option<String>, PipelineError> {
+    let mut command = tokio::process::Command::new(binary);
+    command.arg(version_flag);
+    let output = tokio::time::timeout(TOOL_PROBE_TIMEOUT, command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| PipelineError::Timeout(format!("{} {} 超过 {} 秒未响应", binary, version_flag, TOOL_PROBE_TIMEOUT.as_secs())))?
+        .map_err(|e| PipelineError::ToolNotFound(format!("无法运行 {}: {}", binary, e)))?;
+    
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let stdout = decode_tool_output(&output.stdout);
+    Ok(Some(stdout.lines().next().unwrap_or("").trim().to_string()))
+}
+
+/// 各服务 API 密钥的常见前缀，自定义服务不做检查
+fn api_key_looks_valid(api_key: &str, api_provider: Option<&str>) -> bool {
+    const MIN_API_KEY_LEN: usize = 20;
+    
+    let prefix = match api_provider {
+        Some("custom") => return true,
+        Some("anthropic") => "sk-ant-",
+        Some("gemini") => "AIza",
+        _ => "sk-",
+    };
+    api_key.len() >= MIN_API_KEY_LEN
+        && api_key.starts_with(prefix)
+        && !api_key.chars().any(char::is_whitespace)
+}
+
+/// 计算文件内容的 SHA-256，读取失败时返回 None
+fn hash_file_sha256(path: &str) -> Option<String> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).ok()?;
+    Some(format!("{:x}", hasher.finalize()))
+}
+
+/// 截取片段时把时间段并入 key，同一视频的不同片段各自成为独立的记录
+fn video_key(url: &str, time_range: Option<TimeRange>) -> String {
+    match time_range {
+        Some(range) => format!("{}#t={}-{}", normalize_url(url), range.start, range.end),
+        None => normalize_url(url),
+    }
+}
+
+fn hash_video_key(key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    let result = hasher.finalize();
+    format!("{:x}", result)[..16].to_string() // 取前16位作为ID
+}
+
+/// 为视频分配 vault 中的 id：已有同一视频的记录时沿用其 id；否则 16 位 id 可能与另一个视频的记录冲突，
+/// 这时加盐重新计算直到找到空闲的 id，避免两个视频共用记录和目录
+fn resolve_video_id(vault: &Vault, url: &str, time_range: Option<TimeRange>) -> String {
+    let key = video_key(url, time_range);
+    // 加盐的记录在先占用 id 的记录被删除后仍要能找回，所以按 url 查找而不是重算 id
+    if let Some(existing) = vault.videos.values().find(|record| video_key(&record.url, record.time_range) == key) {
+        return existing.id.clone();
+    }
+    
+    let mut video_id = hash_video_key(&key);
+    let mut salt = 0;
+    while vault.videos.contains_key(&video_id) {
+        salt += 1;
+        video_id = hash_video_key(&format!("{}#salt={}", key, salt));
+    }
+    video_id
+}
+
+/// 把同一视频的不同链接形式统一成规范链接，未识别的站点原样返回
+fn normalize_url(url: &str) -> String {
+    let trimmed = url.trim();
+    let Ok(parsed) = reqwest::Url::parse(trimmed) else {
+        return url.to_string();
+    };
+    
+    if let Some(video_id) = extract_youtube_video_id(&parsed) {
+        return format!("https://www.youtube.com/watch?v={}", video_id);
+    }
+    
+    url.to_string()
+}
+
+/// 从 youtu.be/ID、youtube.com/watch?v=ID、/shorts/ID 等链接中提取视频 ID
+fn extract_youtube_video_id(url: &reqwest::Url) -> Option<String> {
+    let host = url.host_str()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+    
+    let video_id = match host {
+        "youtu.be" => segments.next()?.to_string(),
+        "youtube.com" | "m.youtube.com" | "music.youtube.com" => match segments.next()? {
+            "watch" => url.query_pairs().find(|(key, _)| key == "v")?.1.to_string(),
+            "shorts" | "embed" | "live" | "v" => segments.next()?.to_string(),
+            _ => return None,
         },
-    ];
+        _ => return None,
+    };
+    
+    let is_valid = !video_id.is_empty()
+        && video_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    is_valid.then_some(video_id)
+}
+
+fn expand_tilde_path(path: &str) -> String {
+    expand_tilde_path_with(path, |name| std::env::var_os(name))
+}
+
+/// 展开 `~`、`~/` 和 `~\`，环境变量通过 `lookup` 读取以便替换
+fn expand_tilde_path_with(path: &str, lookup: impl Fn(&str) -> Option<std::ffi::OsString>) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some("") => "",
+        Some(rest) if rest.starts_with('/') || rest.starts_with('\\') => &rest[1..],
+        _ => return path.to_string(),
+    };
+    
+    match home_dir_with(lookup) {
+        Some(home_dir) if rest.is_empty() => home_dir.to_string_lossy().to_string(),
+        Some(home_dir) => home_dir.join(rest).to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// 依次查找 HOME、USERPROFILE 和 HOMEDRIVE + HOMEPATH，Windows 上通常没有 HOME
+fn home_dir_with(lookup: impl Fn(&str) -> Option<std::ffi::OsString>) -> Option<PathBuf> {
+    let non_empty = |name: &str| lookup(name).filter(|value| !value.is_empty());
+    
+    if let Some(home) = non_empty("HOME").or_else(|| non_empty("USERPROFILE")) {
+        return Some(PathBuf::from(home));
+    }
+    
+    match (non_empty("HOMEDRIVE"), non_empty("HOMEPATH")) {
+        (Some(drive), Some(path)) => {
+            let mut home = drive;
+            home.push(path);
+            Some(PathBuf::from(home))
+        }
+        _ => None,
+    }
+}
+
+fn get_vault_path(base_path: &str) -> PathBuf {
+    PathBuf::from(base_path).join("video-transcriber-vault")
+}
+
+/// 根据前端传入的基础目录定位 vault，未指定时使用系统临时目录
+fn resolve_vault_path(base_path: Option<String>) -> PathBuf {
+    let base_dir = base_path.unwrap_or_else(|| std::env::temp_dir().to_string_lossy().to_string());
+    
+    // 展开波浪号路径 (~/Downloads -> /Users/username/Downloads)
+    let expanded_base_dir = expand_tilde_path(&base_dir);
+    
+    get_vault_path(&expanded_base_dir)
+}
+
+fn get_vault_config_path(vault_path: &PathBuf) -> PathBuf {
+    vault_path.join("vault.toml")
+}
+
+fn get_vault_settings_path(vault_path: &Path) -> PathBuf {
+    vault_path.join("config.toml")
+}
+
+fn get_video_dir_path(vault_path: &Path, record: &VideoRecord) -> PathBuf {
+    vault_path.join(record.video_dir.as_deref().unwrap_or(&record.id))
+}
+
+/// 按布局计算视频目录相对 vault 的路径；缺少所需的元数据时返回 None，即使用平铺布局
+fn layout_video_dir(layout: VideoDirLayout, record: &VideoRecord) -> Option<String> {
+    let group = match layout {
+        VideoDirLayout::Flat => None,
+        VideoDirLayout::ByDate => record.upload_date.as_deref()
+            .filter(|date| date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()))
+            .map(|date| format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..])),
+        VideoDirLayout::ByUploader => record.uploader.as_deref().and_then(sanitize_file_name),
+    }?;
+    Some(format!("{}/{}", group, record.id))
+}
+
+/// 把视频目录移动到新位置并更新记录中的文件路径，不删除任何文件。
+/// 原目录不存在时只更新记录；目标已存在时报错，不覆盖。
+fn relocate_video_dir(vault_path: &Path, record: &mut VideoRecord, video_dir: Option<String>) -> Result<(), PipelineError> {
+    let from = get_video_dir_path(vault_path, record);
+    let to = vault_path.join(video_dir.as_deref().unwrap_or(&record.id));
+    if from == to {
+        return Ok(());
+    }
+    ensure_inside_vault(vault_path, &to)?;
+    if to.exists() {
+        return Err(PipelineError::VaultIo(format!("目标目录已存在: {}", to.display())));
+    }
+    
+    if from.is_dir() {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| PipelineError::VaultIo(format!("创建目录失败: {}", e)))?;
+        }
+        fs::rename(&from, &to)
+            .map_err(|e| PipelineError::VaultIo(format!("移动视频目录失败: {}", e)))?;
+        // 分组目录空了就顺手删掉，非空时 remove_dir 会失败，忽略即可
+        if let Some(parent) = from.parent().filter(|parent| *parent != vault_path) {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+    
+    rebase_record_paths(record, &from, &to);
+    record.video_dir = video_dir;
+    Ok(())
+}
+
+/// 把记录中位于 from 之下的文件路径改为 to 之下的相同位置，返回改写的路径数
+fn rebase_record_paths(record: &mut VideoRecord, from: &Path, to: &Path) -> usize {
+    let mut rebased = 0;
+    let mut rebase = |path: &mut String| {
+        if let Ok(relative) = Path::new(path.as_str()).strip_prefix(from) {
+            *path = to.join(relative).to_string_lossy().to_string();
+            rebased += 1;
+        }
+    };
+    for path in [&mut record.audio_file, &mut record.transcript_file, &mut record.translation_file, &mut record.thumbnail_file, &mut record.metadata_file, &mut record.video_file].into_iter().flatten() {
+        rebase(path);
+    }
+    record.subtitle_files.values_mut().for_each(rebase);
+    rebased
+}
+
+/// 缓存中的 vault 及其对应的 vault.toml 内容的 SHA-256
+struct CachedVault {
+    vault: Vault,
+    digest: String,
+}
+
+/// 已解析的 vault，按 vault 路径区分，切换 base_path 后各自独立缓存。
+/// load_vault 在许多没有 AppHandle 的同步函数中调用，所以缓存是进程级的而不是 Tauri 托管的状态。
+/// 每次读取仍会读出 vault.toml 并比较内容摘要，只省去解析和迁移。修改时间和大小不足以判断：
+/// 另一个窗口在同一时间粒度内写入等长的内容时两者都不变
+static VAULT_CACHE: Mutex<Option<HashMap<PathBuf, CachedVault>>> = Mutex::new(None);
+
+fn vault_content_digest(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// 更新缓存，entry 为 None 时移除该 vault 的缓存
+fn cache_vault(vault_path: &Path, entry: Option<(&Vault, String)>) {
+    let mut cache = VAULT_CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    match entry {
+        Some((vault, digest)) => {
+            cache.insert(vault_path.to_path_buf(), CachedVault { vault: vault.clone(), digest });
+        }
+        None => {
+            cache.remove(vault_path);
+        }
+    }
+}
+
+fn load_vault(vault_path: &PathBuf) -> Result<Vault, PipelineError> {
+    let config_path = get_vault_config_path(vault_path);
+    
+    if !config_path.exists() {
+        cache_vault(vault_path, None);
+        // 创建新的vault
+        return Ok(Vault {
+            version: VAULT_VERSION,
+            videos: HashMap::new(),
+        });
+    }
+    
+    match fs::read_to_string(&config_path) {
+        Ok(content) => {
+            let digest = vault_content_digest(&content);
+            {
+                let cache = VAULT_CACHE.lock().unwrap();
+                if let Some(cached) = cache.as_ref().and_then(|cache| cache.get(vault_path.as_path())).filter(|cached| cached.digest == digest) {
+                    return Ok(cached.vault.clone());
+                }
+            }
+            match toml::from_str::<Vault>(&content) {
+                Ok(vault) => {
+                    let vault = migrate_vault(vault)?;
+                    cache_vault(vault_path, Some((&vault, digest)));
+                    Ok(vault)
+                }
+                Err(e) => Err(PipelineError::VaultIo(format!("解析vault配置失败: {}，可用同目录下的 vault.toml.bak 恢复", e)))
+            }
+        }
+        Err(e) => Err(PipelineError::VaultIo(format!("读取vault配置失败: {}", e)))
+    }
+}
+
+/// 把旧版本的 vault 逐级升级到当前版本，升级后的内容在下次保存时写回
+fn migrate_vault(mut vault: Vault) -> Result<Vault, PipelineError> {
+    if vault.version > VAULT_VERSION {
+        return Err(PipelineError::VaultIo(format!("vault 版本 {} 比当前程序支持的版本 {} 新，请升级程序", vault.version, VAULT_VERSION)));
+    }
+    while vault.version < VAULT_VERSION {
+        match vault.version {
+            // v0 -> v1：只新增了版本号，缺失的记录字段已由 serde 默认值补齐
+            0 => {}
+            version => return Err(PipelineError::Internal(format!("缺少 vault 版本 {} 的升级步骤", version))),
+        }
+        vault.version += 1;
+    }
+    Ok(vault)
+}
+
+fn save_vault(vault_path: &PathBuf, vault: &Vault) -> Result<(), PipelineError> {
+    fs::create_dir_all(vault_path)
+        .map_err(|e| PipelineError::VaultIo(format!("创建vault目录失败: {}", e)))?;
+    
+    let config_path = get_vault_config_path(vault_path);
+    let content = toml::to_string_pretty(vault)
+        .map_err(|e| PipelineError::VaultIo(format!("序列化vault配置失败: {}", e)))?;
+    
+    // 覆盖前保留上一版，新内容写坏时还能手动恢复
+    if config_path.is_file() {
+        if let Err(e) = fs::copy(&config_path, config_path.with_extension("toml.bak")) {
+            tracing::warn!("备份vault配置失败: {}", e);
+        }
+    }
+    
+    let saved = write_file_atomically(&config_path, &content)
+        .map_err(|e| PipelineError::VaultIo(format!("保存vault配置失败: {}", e)));
+    // 写入失败时清掉缓存，下次重新读取
+    cache_vault(vault_path, saved.as_ref().ok().map(|_| (vault, vault_content_digest(&content))));
+    saved
+}
+
+/// 先写入同目录下的 `.tmp` 文件再重命名覆盖目标文件。同一文件系统内的重命名是原子的，
+/// 进程在写入途中被杀死时目标文件仍是完整的旧版本
+fn write_file_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// 等待 vault 锁的最长时间
+const VAULT_LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const VAULT_LOCK_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// vault 目录下 `vault.lock` 上的独占建议锁，drop 时释放
+struct VaultLock {
+    file: fs::File,
+}
+
+impl VaultLock {
+    fn open(vault_path: &Path) -> Result<fs::File, PipelineError> {
+        fs::create_dir_all(vault_path)
+            .map_err(|e| PipelineError::VaultIo(format!("创建vault目录失败: {}", e)))?;
+        fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(vault_path.join("vault.lock"))
+            .map_err(|e| PipelineError::VaultIo(format!("打开vault锁文件失败: {}", e)))
+    }
+    
+    /// 尝试加锁一次，锁被其他进程占用时返回 `Ok(false)`
+    fn try_lock(file: &fs::File) -> Result<bool, PipelineError> {
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(false),
+            Err(e) => Err(PipelineError::VaultIo(format!("获取vault锁失败: {}", e))),
+        }
+    }
+    
+    fn acquire(vault_path: &Path) -> Result<Self, PipelineError> {
+        let file = Self::open(vault_path)?;
+        let deadline = std::time::Instant::now() + VAULT_LOCK_TIMEOUT;
+        while !Self::try_lock(&file)? {
+            if std::time::Instant::now() >= deadline {
+                return Err(PipelineError::VaultBusy);
+            }
+            std::thread::sleep(VAULT_LOCK_POLL_INTERVAL);
+        }
+        Ok(VaultLock { file })
+    }
+    
+    /// 异步版本，等待期间让出 tokio 工作线程，供流水线等异步任务使用
+    async fn acquire_async(vault_path: &Path) -> Result<Self, PipelineError> {
+        let file = Self::open(vault_path)?;
+        let deadline = tokio::time::Instant::now() + VAULT_LOCK_TIMEOUT;
+        while !Self::try_lock(&file)? {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(PipelineError::VaultBusy);
+            }
+            tokio::time::sleep(VAULT_LOCK_POLL_INTERVAL).await;
+        }
+        Ok(VaultLock { file })
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// 持锁重新读取 vault、修改后写回。流水线执行期间其他任务可能已写入，
+/// 直接保存启动时读到的副本会覆盖掉它们的进度。
+fn update_vault<T>(vault_path: &PathBuf, update: impl FnOnce(&mut Vault) -> T) -> Result<T, PipelineError> {
+    let _lock = VaultLock::acquire(vault_path)?;
+    let mut vault = load_vault(vault_path)?;
+    let result = update(&mut vault);
+    save_vault(vault_path, &vault)?;
+    Ok(result)
+}
+
+/// `update_vault` 的异步版本，等锁时不阻塞 tokio 工作线程
+async fn update_vault_async<T>(vault_path: &PathBuf, update: impl FnOnce(&mut Vault) -> T) -> Result<T, PipelineError> {
+    let _lock = VaultLock::acquire_async(vault_path).await?;
+    let mut vault = load_vault(vault_path)?;
+    let result = update(&mut vault);
+    save_vault(vault_path, &vault)?;
+    Ok(result)
+}
+
+async fn save_record(vault_path: &PathBuf, record: &VideoRecord) -> Result<(), PipelineError> {
+    update_vault_async(vault_path, |vault| {
+        vault.videos.insert(record.id.clone(), record.clone());
+    }).await
+}
+
+fn load_vault_config(vault_path: &Path) -> Result<VaultConfig, PipelineError> {
+    let config_path = get_vault_settings_path(vault_path);
+    
+    if !config_path.exists() {
+        return Ok(VaultConfig::default());
+    }
+    
+    let content = fs::read_to_string(&config_path)
+        .map_err(|e| PipelineError::VaultIo(format!("读取配置文件失败: {}", e)))?;
+    toml::from_str::<VaultConfig>(&content)
+        .map_err(|e| PipelineError::VaultIo(format!("解析配置文件失败: {}", e)))
+}
+
+fn save_vault_config(vault_path: &Path, config: &VaultConfig) -> Result<(), PipelineError> {
+    fs::create_dir_all(vault_path)
+        .map_err(|e| PipelineError::VaultIo(format!("创建vault目录失败: {}", e)))?;
+    
+    let content = toml::to_string_pretty(config)
+        .map_err(|e| PipelineError::VaultIo(format!("序列化配置失败: {}", e)))?;
+    
+    write_file_atomically(&get_vault_settings_path(vault_path), &content)
+        .map_err(|e| PipelineError::VaultIo(format!("保存配置文件失败: {}", e)))
+}
+
+#[tauri::command]
+fn get_vault_config(base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let config = load_vault_config(&vault_path)?;
+    
+    to_json(&config)
+}
+
+#[tauri::command]
+fn set_vault_config(config: VaultConfig, base_path: Option<String>) -> Result<(), PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    save_vault_config(&vault_path, &config)
+}
+
+fn get_current_timestamp() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    timestamp.to_string()
+}
+
+#[tauri::command]
+async fn select_download_path() -> Result<String, PipelineError> {
+    // 使用系统的文件夹选择对话框
+    let result = rfd::AsyncFileDialog::new()
+        .set_title("选择下载文件夹")
+        .pick_folder()
+        .await;
+        
+    match result {
+        Some(folder) => Ok(folder.path().to_string_lossy().to_string()),
+        None => Err(PipelineError::NotFound("未选择文件夹".to_string()))
+    }
+}
+
+#[tauri::command]
+async fn process_video_pipeline(app: AppHandle, registry: State<'_, PipelineRegistry>, url: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let options = options.unwrap_or_default().with_api(api_key, api_provider);
+    let vault_path = resolve_vault_path(base_path);
+    
+    let mut trace = PipelineTrace::default();
+    let record = run_pipeline(&app, &registry, &url, &vault_path, &options, &mut trace).await?;
+    
+    // 返回结果
+    to_json(&PipelineResult::new(record, trace))
+}
+
+/// 返回处理该链接时流水线使用的视频 id，前端据此只接收自己启动的流水线的进度事件
+#[tauri::command]
+fn pipeline_video_id(url: String, base_path: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let options = options.unwrap_or_default().with_defaults(&load_vault_config(&vault_path)?);
+    let time_range = DownloadOptions::from_pipeline_options(&options).time_range()?;
+    Ok(resolve_video_id(&load_vault(&vault_path)?, &url, time_range))
+}
+
+/// 只查询视频信息并对照 vault 中的进度，返回实际运行时各步骤会执行还是跳过，不下载、不转录也不写入任何文件
+#[tauri::command]
+async fn preview_pipeline(url: String, base_path: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let config = load_vault_config(&vault_path)?;
+    let options = options.unwrap_or_default().with_defaults(&config);
+    
+    let mut download_options = DownloadOptions::from_pipeline_options(&options);
+    download_options.validate()?;
+    TranscribeOptions::from_pipeline_options(&options).validate()?;
+    let time_range = download_options.time_range()?;
+    
+    let vault = load_vault(&vault_path)?;
+    let video_id = resolve_video_id(&vault, &url, time_range);
+    let existing = vault.videos.get(&video_id);
+    
+    // 本地文件直接用文件名作为标题；网络视频只打印需要的字段，不导出元数据
+    let (title, duration_seconds) = match local_file_path(&url) {
+        Some(source) => {
+            if time_range.is_some() {
+                return Err(PipelineError::InvalidInput("本地文件不支持截取片段".to_string()));
+            }
+            let title = source.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+            (title, existing.and_then(|record| record.duration_seconds))
+        }
+        None => {
+            download_options.save_metadata = false;
+            let info = fetch_video_info(&url, &download_options).await?;
+            let duration = time_range.map(|range| range.end - range.start).or(info.duration_seconds);
+            (info.title, duration)
+        }
+    };
+    
+    let stage = |name: StageName, done: bool, detail: Option<String>| {
+        if done {
+            PipelineStage::new(name, StageStatus::Skipped, detail)
+        } else {
+            PipelineStage::new(name, StageStatus::Pending, None)
+        }
+    };
+    let stages = match existing {
+        Some(record) => {
+            let transcribed = record.transcribed && !options.force_retranscribe;
+            // 音频已删除且需要转录时会重新下载
+            let downloaded = record.downloaded && (!record.audio_purged || transcribed) && !options.force_redownload;
+            vec![
+                stage(StageName::Download, downloaded, record.audio_file.clone()),
+                stage(StageName::Transcribe, transcribed, record.transcript_file.clone()),
+                stage(StageName::Summarize, record.summarized, record.summary_provider.clone()),
+            ]
+        }
+        None => vec![
+            stage(StageName::Download, false, None),
+            stage(StageName::Transcribe, false, None),
+            stage(StageName::Summarize, false, None),
+        ],
+    };
+    
+    to_json(&PipelinePlan {
+        video_id,
+        title,
+        duration_seconds,
+        existing: existing.is_some(),
+        stages,
+    })
+}
+
+/// 直接转录本地的音频或视频文件，跳过下载步骤
+#[tauri::command]
+async fn process_local_file(app: AppHandle, registry: State<'_, PipelineRegistry>, file_path: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let options = options.unwrap_or_default().with_api(api_key, api_provider);
+    let vault_path = resolve_vault_path(base_path);
+    
+    let source = validate_local_media_file(&file_path)?;
+    // 用 file:// URI 作为记录的 url，视频 id 由文件的绝对路径生成
+    let url = reqwest::Url::from_file_path(&source)
+        .map_err(|_| PipelineError::InvalidInput(format!("无法转换为文件 URI: {}", source.display())))?
+        .to_string();
+    
+    let mut trace = PipelineTrace::default();
+    let record = run_pipeline(&app, &registry, &url, &vault_path, &options, &mut trace).await?;
+    to_json(&PipelineResult::new(record, trace))
+}
+
+/// 等待导入的录音在 vault 中的暂存目录，以 `.` 开头，不会被当作孤立目录清理
+const RECORDINGS_DIR: &str = ".recordings";
+/// 暂存录音的文件名，也是录音记录的默认标题
+const RECORDING_FILE_NAME: &str = "录音.wav";
+
+/// 检查文件是 RIFF/WAVE 格式且可以读取
+fn validate_wav_file(path: &Path) -> Result<(), PipelineError> {
+    let mut header = [0u8; 12];
+    let mut file = fs::File::open(path)
+        .map_err(|e| PipelineError::InvalidInput(format!("无法读取录音文件 {}: {}", path.display(), e)))?;
+    std::io::Read::read_exact(&mut file, &mut header)
+        .map_err(|_| PipelineError::InvalidInput(format!("录音文件为空或不完整: {}", path.display())))?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(PipelineError::InvalidInput(format!("录音文件不是有效的 WAV 文件: {}", path.display())));
+    }
+    Ok(())
+}
+
+/// 转录前端刚录好的 WAV 录音并生成总结。录音先复制到暂存目录下以内容哈希命名的子目录，
+/// 再按本地文件导入处理，所以记录 id 由录音内容决定，同一段录音重复提交时沿用已有记录。
+/// 导入视频目录后删除暂存的副本；未能导入时保留，之后可以用 retry_video 或 resume_all 继续
+#[tauri::command]
+async fn transcribe_recording(app: AppHandle, registry: State<'_, PipelineRegistry>, audio_path: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let options = options.unwrap_or_default().with_api(api_key, api_provider);
+    let vault_path = resolve_vault_path(base_path);
+    
+    let source = validate_local_media_file(&audio_path)?;
+    if !source.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("wav")) {
+        return Err(PipelineError::InvalidInput(format!("录音文件需要是 .wav 格式: {}", source.display())));
+    }
+    validate_wav_file(&source)?;
+    let hash = hash_file_sha256(&source.to_string_lossy())
+        .ok_or_else(|| PipelineError::InvalidInput(format!("无法读取录音文件: {}", source.display())))?;
+    
+    let recording_dir = vault_path.join(RECORDINGS_DIR).join(&hash[..16]);
+    let staged = recording_dir.join(RECORDING_FILE_NAME);
+    if !staged.is_file() {
+        fs::create_dir_all(&recording_dir)
+            .and_then(|_| fs::copy(&source, &staged))
+            .map_err(|e| PipelineError::VaultIo(format!("保存录音失败: {}", e)))?;
+    }
+    let url = reqwest::Url::from_file_path(&staged)
+        .map_err(|_| PipelineError::InvalidInput(format!("无法转换为文件 URI: {}", staged.display())))?
+        .to_string();
+    
+    let mut trace = PipelineTrace::default();
+    let record = run_pipeline(&app, &registry, &url, &vault_path, &options, &mut trace).await?;
+    if record.downloaded {
+        if let Err(e) = fs::remove_dir_all(&recording_dir) {
+            tracing::warn!("删除暂存的录音失败 {}: {}", recording_dir.display(), e);
+        }
+    }
+    to_json(&PipelineResult::new(record, trace))
+}
+
+#[tauri::command]
+async fn process_playlist(app: AppHandle, registry: State<'_, PipelineRegistry>, url: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let options = options.unwrap_or_default().with_api(api_key, api_provider);
+    let vault_path = resolve_vault_path(base_path);
+    
+    // 整个播放列表也登记为一条流水线，cancel_pipeline(播放列表 id) 会在处理下一个条目前停止
+    let playlist_id = playlist_pipeline_id(&url);
+    let progress = ProgressEmitter { app: app.clone(), video_id: playlist_id.clone() };
+    let running = registry.start(&playlist_id)?;
+    
+    let download_options = DownloadOptions::from_pipeline_options(&options.clone().with_defaults(&load_vault_config(&vault_path)?));
+    download_options.validate()?;
+    let entries = list_playlist_entries(&url, &download_options).await?;
+    progress.emit("playlist", Some(0.0), &format!("播放列表共 {} 个视频", entries.len()));
+    
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+    
+    for (index, entry_url) in entries.iter().enumerate() {
+        if running.token.is_cancelled() {
+            break;
+        }
+        
+        // 单个条目失败不影响其余条目，错误通过进度事件告知前端
+        let mut trace = PipelineTrace::default();
+        match run_pipeline(&app, &registry, entry_url, &vault_path, &options, &mut trace).await {
+            Ok(record) => records.push(PipelineResult::new(record, trace)),
+            Err(e) => {
+                progress.emit("playlist", None, &format!("处理失败 {}: {}", entry_url, e));
+                errors.push(e);
+            }
+        }
+        
+        let percent = (index + 1) as f32 / entries.len() as f32 * 100.0;
+        progress.emit("playlist", Some(percent), &format!("已处理 {}/{}", index + 1, entries.len()));
+    }
+    
+    // 全部失败时返回第一个错误，各条目的错误已通过进度事件逐一告知
+    if records.is_empty() && !errors.is_empty() {
+        return Err(errors.remove(0));
+    }
+    
+    to_json(&records)
+}
+
+/// 播放列表流水线的 id。不经过 normalize_url：`watch?v=X&list=Y` 会被规范成视频 X 的链接，
+/// 与条目 X 的流水线 id 相同，登记条目时就会因 AlreadyRunning 失败
+fn playlist_pipeline_id(url: &str) -> String {
+    hash_video_key(&format!("playlist:{}", url.trim()))
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum BatchItemStatus {
+    Done,
+    Failed,
+    /// 与批次中前面的链接指向同一个视频，未重复处理
+    Duplicate,
+    /// 批次被取消，未处理
+    Cancelled,
+    /// 记录缺少链接或音频已无法找回，未处理，原因见 warnings
+    Skipped,
+}
+
+#[derive(Serialize)]
+struct BatchItemResult {
+    url: String,
+    video_id: String,
+    status: BatchItemStatus,
+    /// 失败时可以看出是哪一步出错
+    stages: Vec<PipelineStage>,
+    warnings: Vec<String>,
+    error: Option<PipelineError>,
+}
+
+/// 批量处理时同时运行的流水线数上限，转录仍受 TranscriptionQueue 限制
+const MAX_BATCH_CONCURRENCY: usize = 8;
+
+/// 推送给前端的 `batch-progress` 事件负载
+#[derive(Serialize, Clone)]
+struct BatchProgress {
+    batch_id: String,
+    completed: usize,
+    total: usize,
+    in_progress: usize,
+}
+
+/// 批次流水线的 id。只有一个链接时 `urls.join` 就是该链接本身，不加前缀会与该视频的流水线 id 相同
+fn batch_pipeline_id(urls: &[String]) -> String {
+    hash_video_key(&format!("batch:{}", urls.join("\n")))
+}
+
+/// 处理多个链接，最多 concurrency 个同时进行，单个失败不影响其余链接；按输入顺序返回每个链接的处理结果
+#[tauri::command]
+async fn process_batch(app: AppHandle, registry: State<'_, PipelineRegistry>, urls: Vec<String>, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    // 与 pipeline_video_id 一样先套用 vault 配置，去重和分配的 id 才与实际运行时一致
+    let options = options.unwrap_or_default()
+        .with_api(api_key, api_provider)
+        .with_defaults(&load_vault_config(&vault_path)?);
+    let concurrency = options.batch_concurrency.unwrap_or(1);
+    if !(1..=MAX_BATCH_CONCURRENCY).contains(&concurrency) {
+        return Err(PipelineError::InvalidInput(format!("批量处理并发数必须在 1 到 {} 之间", MAX_BATCH_CONCURRENCY)));
+    }
+    
+    let urls: Vec<String> = urls.iter()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    if urls.is_empty() {
+        return Err(PipelineError::InvalidInput("没有需要处理的链接".to_string()));
+    }
+    
+    // 整个批次也登记为一条流水线，cancel_pipeline(批次 id) 后尚未开始的链接不再处理
+    let batch_id = batch_pipeline_id(&urls);
+    let progress = ProgressEmitter { app: app.clone(), video_id: batch_id.clone() };
+    let running = registry.start(&batch_id)?;
+    progress.emit("batch", Some(0.0), &format!("批量处理共 {} 个链接", urls.len()));
+    
+    let time_range = DownloadOptions::from_pipeline_options(&options).time_range()?;
+    // 先按输入顺序找出重复的链接，并行处理时也只保留第一次出现的。
+    // 按规范化后的链接判断重复，id 与 run_pipeline 一样用 resolve_video_id 分配，沿用已有记录或加盐后的 id
+    let vault = load_vault(&vault_path)?;
+    let mut seen = HashSet::new();
+    let items: Vec<(usize, String, String, bool)> = urls.iter()
+        .enumerate()
+        .map(|(index, url)| {
+            let duplicate = !seen.insert(video_key(url, time_range));
+            (index, url.clone(), resolve_video_id(&vault, url, time_range), duplicate)
+        })
+        .collect();
+    drop(vault);
+    
+    let total = urls.len();
+    let completed = std::sync::atomic::AtomicUsize::new(0);
+    let in_progress = std::sync::atomic::AtomicUsize::new(0);
+    let emit_batch_progress = || {
+        let payload = BatchProgress {
+            batch_id: batch_id.clone(),
+            completed: completed.load(std::sync::atomic::Ordering::SeqCst),
+            total,
+            in_progress: in_progress.load(std::sync::atomic::Ordering::SeqCst),
+        };
+        if let Err(e) = app.emit("batch-progress", payload) {
+            tracing::warn!("发送进度事件失败: {}", e);
+        }
+    };
+    
+    // 各链接共享以下状态的引用，闭包按值捕获这些引用
+    let (app, registry, vault_path, options, progress) = (&app, &registry, &vault_path, &options, &progress);
+    let (cancel, completed, in_progress, emit_batch_progress) = (&running.token, &completed, &in_progress, &emit_batch_progress);
+    let process_item = move |(index, url, mut video_id, duplicate): (usize, String, String, bool)| {
+        async move {
+            let mut trace = PipelineTrace::default();
+            let (status, error) = if cancel.is_cancelled() {
+                (BatchItemStatus::Cancelled, None)
+            } else if duplicate {
+                (BatchItemStatus::Duplicate, None)
+            } else {
+                in_progress.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                emit_batch_progress();
+                let result = run_pipeline(app, registry, &url, vault_path, options, &mut trace).await;
+                in_progress.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                match result {
+                    Ok(record) => {
+                        // id 冲突时实际使用的是加盐后的 id
+                        video_id = record.id;
+                        (BatchItemStatus::Done, None)
+                    }
+                    Err(e) => {
+                        progress.emit("batch", None, &format!("处理失败 {}: {}", url, e));
+                        (BatchItemStatus::Failed, Some(e))
+                    }
+                }
+            };
+            
+            let done = completed.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            progress.emit("batch", Some(done as f32 / total as f32 * 100.0), &format!("已处理 {}/{}", done, total));
+            emit_batch_progress();
+            (index, BatchItemResult { url, video_id, status, stages: trace.stages, warnings: trace.warnings, error })
+        }
+    };
+    
+    // 各链接完成的先后不定，结果按输入顺序排列
+    let mut results: Vec<(usize, BatchItemResult)> = futures_util::stream::iter(items)
+        .map(process_item)
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    results.sort_by_key(|(index, _)| *index);
+    let results: Vec<BatchItemResult> = results.into_iter().map(|(_, result)| result).collect();
+    
+    to_json(&results)
+}
+
+/// list_videos 的筛选条件，未指定的条件不参与筛选，指定的条件需要同时满足
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct VideoFilter {
+    transcribed: Option<bool>,
+    summarized: Option<bool>,
+    /// 标签完全一致，不区分大小写
+    tag: Option<String>,
+    /// 标题包含该文字，不区分大小写
+    title_contains: Option<String>,
+}
+
+impl VideoFilter {
+    fn matches(&self, record: &VideoRecord) -> bool {
+        let tag = self.tag.as_deref().map(|tag| fold_case(tag.trim()));
+        let title_contains = self.title_contains.as_deref().map(|text| fold_case(text.trim()));
+        self.transcribed.is_none_or(|transcribed| record.transcribed == transcribed)
+            && self.summarized.is_none_or(|summarized| record.summarized == summarized)
+            && tag.is_none_or(|tag| record.tags.iter().any(|candidate| fold_case(candidate) == tag))
+            && title_contains.is_none_or(|text| record.title.as_deref().is_some_and(|title| fold_case(title).contains(&text)))
+    }
+}
+
+#[tauri::command]
+fn list_videos(base_path: Option<String>, filter: Option<VideoFilter>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let filter = filter.unwrap_or_default();
+    
+    // vault 尚未创建时 load_vault 返回空列表
+    let vault = load_vault(&vault_path)?;
+    
+    let mut videos: Vec<VideoRecord> = vault.videos.into_values()
+        .filter(|record| filter.matches(record))
+        .collect();
+    // 时间戳是秒数字符串，按数值而不是字典序排序
+    videos.sort_by_key(|video| std::cmp::Reverse(video.updated_at.parse::<u64>().unwrap_or(0)));
+    
+    to_json(&videos)
+}
+
+/// 读取单个视频的完整记录，供详情页展示，不会触发任何处理
+#[tauri::command]
+fn get_video(video_id: String, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    to_json(record)
+}
+
+/// 只读取转录文本，供复制到剪贴板等不需要完整记录的场景。
+/// 记录不存在或转录文件已丢失时返回 NotFound，还没有转录时返回 InvalidInput
+#[tauri::command]
+fn get_transcript(video_id: String, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    if let Some(content) = &record.transcript_content {
+        return Ok(content.clone());
+    }
+    match &record.transcript_file {
+        Some(transcript_file) if Path::new(transcript_file).is_file() => read_text_file(transcript_file)
+            .map(|content| content.trim().to_string())
+            .map_err(|e| PipelineError::VaultIo(format!("读取转录文件失败: {}", e))),
+        Some(transcript_file) => Err(PipelineError::NotFound(format!("转录文件已不存在: {}", transcript_file))),
+        None => Err(PipelineError::InvalidInput("该视频还没有转录".to_string())),
+    }
+}
+
+/// 片段中命中位置前后各保留的字符数
+const SEARCH_SNIPPET_CONTEXT_CHARS: usize = 60;
+
+#[derive(Serialize)]
+struct SearchHit {
+    video_id: String,
+    title: Option<String>,
+    snippet: String,
+}
+
+#[tauri::command]
+fn search_transcripts(query: String, base_path: Option<String>) -> Result<String, PipelineError> {
+    let terms: Vec<String> = query.split_whitespace().map(fold_case).collect();
+    if terms.is_empty() {
+        return Err(PipelineError::InvalidInput("搜索关键词不能为空".to_string()));
+    }
+    
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    
+    let mut videos: Vec<&VideoRecord> = vault.videos.values().collect();
+    videos.sort_by_key(|video| std::cmp::Reverse(video.updated_at.parse::<u64>().unwrap_or(0)));
+    
+    // 多个关键词之间是 AND 关系，片段取第一个关键词首次出现的位置
+    let hits: Vec<SearchHit> = videos.into_iter()
+        .filter_map(|video| {
+            let transcript = video.transcript_content.as_ref()?;
+            let folded = fold_case(transcript);
+            if !terms.iter().all(|term| folded.contains(term.as_str())) {
+                return None;
+            }
+            
+            let byte_index = folded.find(terms[0].as_str())?;
+            let char_index = folded[..byte_index].chars().count();
+            Some(SearchHit {
+                video_id: video.id.clone(),
+                title: video.title.clone(),
+                snippet: extract_snippet(transcript, char_index, terms[0].chars().count()),
+            })
+        })
+        .collect();
+    
+    to_json(&hits)
+}
+
+/// 逐字符转小写且保持字符数不变，使折叠后的下标可以直接对应回原文
+fn fold_case(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+fn extract_snippet(text: &str, char_index: usize, match_chars: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let start = char_index.saturating_sub(SEARCH_SNIPPET_CONTEXT_CHARS);
+    let end = (char_index + match_chars + SEARCH_SNIPPET_CONTEXT_CHARS).min(chars.len());
+    
+    let mut snippet: String = chars[start..end].iter().collect();
+    snippet = snippet.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if start > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+/// 删除视频记录和目录。流水线运行中的视频会被拒绝，避免删除后又被流水线写回
+#[tauri::command]
+fn delete_video(registry: State<'_, PipelineRegistry>, video_id: String, base_path: Option<String>) -> Result<(), PipelineError> {
+    if registry.running.lock().unwrap().contains_key(&video_id) {
+        return Err(PipelineError::AlreadyRunning(format!("视频 {} 的流水线正在运行，请等待完成后再删除", video_id)));
+    }
+    remove_video(&resolve_vault_path(base_path), &video_id)
+}
+
+fn remove_video(vault_path: &PathBuf, video_id: &str) -> Result<(), PipelineError> {
+    // 没有记录时按平铺布局清理可能残留的目录
+    let video_dir = match load_vault(vault_path)?.videos.get(video_id) {
+        Some(record) => get_video_dir_path(vault_path, record),
+        None => vault_path.join(video_id),
+    };
+    ensure_inside_vault(vault_path, &video_dir)?;
+    
+    update_vault(vault_path, |vault| {
+        vault.videos.remove(video_id);
+    })?;
+    
+    // 记录或目录已不存在时视为删除成功
+    if video_dir.exists() {
+        fs::remove_dir_all(&video_dir)
+            .map_err(|e| PipelineError::VaultIo(format!("删除视频目录失败: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// 手动修改视频标题，只更新 vault 中的记录，不改动磁盘上的文件
+#[tauri::command]
+fn update_video_title(video_id: String, new_title: String, base_path: Option<String>) -> Result<(), PipelineError> {
+    let title = new_title.trim();
+    if title.is_empty() {
+        return Err(PipelineError::InvalidInput("标题不能为空".to_string()));
+    }
+    
+    update_video_record(&video_id, base_path, |record| record.title = Some(title.to_string()))
+}
+
+/// 修改视频备注，内容为空时清除备注
+#[tauri::command]
+fn set_video_notes(video_id: String, notes: Option<String>, base_path: Option<String>) -> Result<(), PipelineError> {
+    let notes = notes.map(|notes| notes.trim().to_string()).filter(|notes| !notes.is_empty());
+    update_video_record(&video_id, base_path, |record| record.notes = notes)
+}
+
+/// 替换视频的全部标签。标签去掉首尾空白后去重，保留原有顺序
+#[tauri::command]
+fn set_video_tags(video_id: String, tags: Vec<String>, base_path: Option<String>) -> Result<(), PipelineError> {
+    let mut seen = HashSet::new();
+    let tags: Vec<String> = tags.iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .collect();
+    update_video_record(&video_id, base_path, |record| record.tags = tags)
+}
+
+/// 合并指向同一视频的两条记录：保留 keep_id，删除 remove_id 的记录和目录。
+/// keep_id 缺少的下载、转录、总结结果整体取自 remove_id，相应文件移入 keep_id 的目录；
+/// 其他字段以 keep_id 为准，缺失时才用 remove_id 的值；标签取并集，备注前后拼接
+#[tauri::command]
+fn merge_videos(registry: State<'_, PipelineRegistry>, keep_id: String, remove_id: String, base_path: Option<String>) -> Result<String, PipelineError> {
+    if keep_id == remove_id {
+        return Err(PipelineError::InvalidInput("不能把视频与自身合并".to_string()));
+    }
+    {
+        let running = registry.running.lock().unwrap();
+        if let Some(id) = [&keep_id, &remove_id].into_iter().find(|id| running.contains_key(*id)) {
+            return Err(PipelineError::AlreadyRunning(format!("视频 {} 的流水线正在运行，请等待完成后再合并", id)));
+        }
+    }
+    
+    let vault_path = resolve_vault_path(base_path);
+    let _lock = VaultLock::acquire(&vault_path)?;
+    let mut vault = load_vault(&vault_path)?;
+    let mut keep = vault.videos.get(&keep_id).cloned()
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", keep_id)))?;
+    let remove = vault.videos.get(&remove_id).cloned()
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", remove_id)))?;
+    let keep_dir = get_video_dir_path(&vault_path, &keep);
+    let remove_dir = get_video_dir_path(&vault_path, &remove);
+    ensure_inside_vault(&vault_path, &remove_dir)?;
+    
+    if !keep.downloaded && remove.downloaded {
+        keep.downloaded = true;
+        keep.audio_file = remove.audio_file.clone();
+        keep.audio_format = remove.audio_format.clone();
+        keep.audio_size_bytes = remove.audio_size_bytes;
+        keep.audio_sha256 = remove.audio_sha256.clone();
+        keep.audio_purged = remove.audio_purged;
+        keep.authenticated_download = remove.authenticated_download;
+        keep.format_selector = remove.format_selector.clone();
+        keep.custom_headers = remove.custom_headers;
+    }
+    if !keep.transcribed && remove.transcribed {
+        keep.transcribed = true;
+        keep.transcript_file = remove.transcript_file.clone();
+        keep.transcript_content = remove.transcript_content.clone();
+        keep.subtitle_files = remove.subtitle_files.clone();
+        keep.segments = remove.segments.clone();
+        keep.whisper_model = remove.whisper_model.clone();
+        keep.transcribe_language = remove.transcribe_language.clone();
+        keep.chunked_transcription = None;
+    }
+    if !keep.summarized && remove.summarized {
+        keep.summarized = true;
+        keep.summary_content = remove.summary_content.clone();
+        keep.summary_provider = remove.summary_provider.clone();
+        keep.summary_model = remove.summary_model.clone();
+        keep.summary_style = remove.summary_style;
+        keep.keywords = remove.keywords.clone();
+        keep.summaries = remove.summaries.clone();
+        keep.chapter_summaries = remove.chapter_summaries.clone();
+        keep.word_count = remove.word_count;
+        keep.reading_minutes = remove.reading_minutes;
+    }
+    if keep.translation_content.is_none() {
+        keep.translation_content = remove.translation_content.clone();
+        keep.translation_file = remove.translation_file.clone();
+    }
+    keep.title = keep.title.or(remove.title);
+    keep.duration_seconds = keep.duration_seconds.or(remove.duration_seconds);
+    keep.thumbnail_file = keep.thumbnail_file.or(remove.thumbnail_file);
+    keep.video_file = keep.video_file.or(remove.video_file);
+    keep.metadata_file = keep.metadata_file.or(remove.metadata_file);
+    keep.upload_date = keep.upload_date.or(remove.upload_date);
+    keep.uploader = keep.uploader.or(remove.uploader);
+    keep.notes = match (keep.notes, remove.notes) {
+        (Some(kept), Some(removed)) if kept != removed => Some(format!("{}\n\n{}", kept, removed)),
+        (kept, removed) => kept.or(removed),
+    };
+    for tag in remove.tags {
+        if !keep.tags.contains(&tag) {
+            keep.tags.push(tag);
+        }
+    }
+    // 时间戳是秒数字符串，保留较早的创建时间
+    if remove.created_at.parse::<u64>().unwrap_or(u64::MAX) < keep.created_at.parse::<u64>().unwrap_or(u64::MAX) {
+        keep.created_at = remove.created_at;
+    }
+    keep.updated_at = get_current_timestamp();
+    
+    // 取自被删除记录的文件位于其目录下，删除目录前先移过来。先核对全部目标再移动，
+    // 移动或保存失败时把已移动的文件移回原处，vault 中的记录仍指向原位置
+    let mut moves: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut adopt = |path: &mut String| {
+        let Ok(relative) = Path::new(path.as_str()).strip_prefix(&remove_dir) else {
+            return;
+        };
+        let source = PathBuf::from(path.as_str());
+        let target = keep_dir.join(relative);
+        *path = target.to_string_lossy().to_string();
+        if !moves.iter().any(|(from, _)| *from == source) {
+            moves.push((source, target));
+        }
+    };
+    for path in [&mut keep.audio_file, &mut keep.transcript_file, &mut keep.translation_file, &mut keep.thumbnail_file, &mut keep.metadata_file, &mut keep.video_file].into_iter().flatten() {
+        adopt(path);
+    }
+    keep.subtitle_files.values_mut().for_each(adopt);
+    if let Some((_, target)) = moves.iter().find(|(_, target)| target.exists()) {
+        return Err(PipelineError::VaultIo(format!("目标文件已存在: {}", target.display())));
+    }
+    
+    let mut moved = 0;
+    let merged = moves.iter()
+        .try_for_each(|(source, target)| {
+            target.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::rename(source, target))
+                .map_err(|e| PipelineError::VaultIo(format!("移动文件失败 {}: {}", source.display(), e)))?;
+            moved += 1;
+            Ok(())
+        })
+        .and_then(|_| {
+            vault.videos.remove(&remove_id);
+            vault.videos.insert(keep_id, keep.clone());
+            save_vault(&vault_path, &vault)
+        });
+    if let Err(e) = merged {
+        for (source, target) in moves[..moved].iter().rev() {
+            if let Err(rollback) = fs::rename(target, source) {
+                tracing::warn!("移回文件失败 {}: {}", target.display(), rollback);
+            }
+        }
+        return Err(e);
+    }
+    
+    if remove_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&remove_dir) {
+            tracing::warn!("删除被合并视频的目录失败 {}: {}", remove_dir.display(), e);
+        }
+    }
+    
+    to_json(&keep)
+}
+
+/// 只修改 vault 中的一条记录并更新时间戳，记录不存在时返回 NotFound
+fn update_video_record(video_id: &str, base_path: Option<String>, update: impl FnOnce(&mut VideoRecord)) -> Result<(), PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let found = update_vault(&vault_path, |vault| {
+        match vault.videos.get_mut(video_id) {
+            Some(record) => {
+                update(record);
+                record.updated_at = get_current_timestamp();
+                true
+            }
+            None => false,
+        }
+    })?;
+    
+    if found {
+        Ok(())
+    } else {
+        Err(PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))
+    }
+}
+
+/// 在系统文件管理器（Finder / 资源管理器）中打开视频目录
+#[tauri::command]
+fn open_video_folder(app: AppHandle, video_id: String, base_path: Option<String>) -> Result<(), PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    let video_dir = get_video_dir_path(&vault_path, record);
+    ensure_inside_vault(&vault_path, &video_dir)?;
+    if !video_dir.is_dir() {
+        return Err(PipelineError::NotFound(format!("视频目录不存在: {}", video_dir.display())));
+    }
+    
+    app.opener()
+        .open_path(video_dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| PipelineError::Internal(format!("打开文件夹失败: {}", e)))
+}
+
+/// 在视频目录下生成 `<标题>.md`，包含来源、时长、总结和完整转录，返回写入的路径
+#[tauri::command]
+fn export_video_markdown(video_id: String, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    let video_dir = get_video_dir_path(&vault_path, record);
+    ensure_inside_vault(&vault_path, &video_dir)?;
+    
+    fs::create_dir_all(&video_dir)
+        .map_err(|e| PipelineError::VaultIo(format!("创建视频目录失败: {}", e)))?;
+    
+    let title = record.title.as_deref().unwrap_or(&record.id);
+    let file_name = format!("{}.md", sanitize_file_name(title).unwrap_or_else(|| record.id.clone()));
+    let markdown_path = video_dir.join(file_name);
+    
+    fs::write(&markdown_path, render_video_markdown(record))
+        .map_err(|e| PipelineError::VaultIo(format!("写入 Markdown 文件失败: {}", e)))?;
+    
+    Ok(markdown_path.to_string_lossy().to_string())
+}
+
+fn render_video_markdown(record: &VideoRecord) -> String {
+    let title = record.title.as_deref().unwrap_or("未知标题");
+    let duration = record.duration_seconds
+        .map(format_duration)
+        .unwrap_or_else(|| "未知".to_string());
+    
+    let mut markdown = format!("# {}\n\n- 来源: <{}>\n- 时长: {}\n", title, record.url, duration);
+    if let Some(model) = &record.whisper_model {
+        markdown.push_str(&format!("- 转录模型: {}\n", model));
+    }
+    
+    markdown.push_str("\n## 总结\n\n");
+    markdown.push_str(record.summary_content.as_deref().unwrap_or("（暂无总结）").trim());
+    markdown.push_str("\n\n## 转录\n\n");
+    markdown.push_str(record.transcript_content.as_deref().unwrap_or("（暂无转录）").trim());
+    markdown.push('\n');
+    markdown
+}
+
+/// 秒数格式化为 `H:MM:SS` 或 `M:SS`
+fn format_duration(total_seconds: u64) -> String {
+    let hours = total_seconds / 3600;
+    let minutes = total_seconds % 3600 / 60;
+    let seconds = total_seconds % 60;
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// 去掉文件名中各平台不允许的字符，清理后为空时返回 None
+fn sanitize_file_name(name: &str) -> Option<String> {
+    const MAX_FILE_NAME_CHARS: usize = 120;
+    
+    let sanitized: String = name.chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .take(MAX_FILE_NAME_CHARS)
+        .collect();
+    // Windows 不允许文件名以空格或点结尾，开头的点会变成隐藏文件
+    let sanitized = sanitized.trim().trim_matches('.').trim();
+    
+    if sanitized.is_empty() {
+        None
+    } else {
+        Some(sanitized.to_string())
+    }
+}
+
+/// 记录引用了但磁盘上不存在的内容
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum MissingItem {
+    VideoDir,
+    AudioFile,
+    TranscriptFile,
+    SubtitleFile,
+}
+
+#[derive(Serialize)]
+struct VaultIssue {
+    video_id: String,
+    missing: MissingItem,
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct VaultReport {
+    checked: usize,
+    issues: Vec<VaultIssue>,
+}
+
+/// 导出的 vault：记录与 Vault 的 JSON 结构相同，`files` 为可选附带的文本文件内容
+#[derive(Serialize, Deserialize)]
+struct VaultBundle {
+    #[serde(flatten)]
+    vault: Vault,
+    /// 导出时 vault 的路径，导入时据此把记录中的文件路径换到新的 vault 下
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_path: Option<String>,
+    /// 相对 vault 的路径 -> 文件内容，只包含转录、字幕和元数据等文本文件，不含音频
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    files: HashMap<String, String>,
+}
+
+/// 把整个 vault 导出为 JSON，用于备份或迁移到其他电脑。`include_files` 为 true 时附带文本文件内容
+#[tauri::command]
+fn export_vault(base_path: Option<String>, include_files: bool) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    
+    let mut files = HashMap::new();
+    if include_files {
+        for record in vault.videos.values() {
+            let text_files = record.transcript_file.iter()
+                .chain(record.translation_file.iter())
+                .chain(record.subtitle_files.values())
+                .chain(record.metadata_file.iter());
+            for path in text_files {
+                let Ok(relative) = Path::new(path).strip_prefix(&vault_path) else { continue };
+                match fs::read_to_string(path) {
+                    Ok(content) => {
+                        files.insert(bundle_file_key(relative), content);
+                    }
+                    Err(e) => tracing::warn!("导出时读取文件失败 {}: {}", path, e),
+                }
+            }
+        }
+    }
+    
+    to_json(&VaultBundle {
+        vault,
+        source_path: include_files.then(|| vault_path.to_string_lossy().to_string()),
+        files,
+    })
+}
+
+/// 导出文件的键统一用 / 分隔，在其他系统上导入时同样可用
+fn bundle_file_key(relative: &Path) -> String {
+    relative.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Serialize)]
+struct VaultImportReport {
+    imported: Vec<String>,
+    /// 当前 vault 中已存在、未导入的 id
+    skipped: Vec<String>,
+}
+
+/// 把 export_vault 导出的内容合并到当前 vault，已存在的 id 跳过。音频不随导出迁移，
+/// 导入的记录标记为未下载，需要时由流水线重新下载
+#[tauri::command]
+fn import_vault(bundle: String, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let bundle: VaultBundle = serde_json::from_str(&bundle)
+        .map_err(|e| PipelineError::InvalidInput(format!("解析导入内容失败: {}", e)))?;
+    let source_path = bundle.source_path.as_deref().map(Path::new);
+    
+    let report = update_vault(&vault_path, |vault| {
+        let mut report = VaultImportReport { imported: Vec::new(), skipped: Vec::new() };
+        
+        for (video_id, mut record) in bundle.vault.videos {
+            if vault.videos.contains_key(&video_id) {
+                report.skipped.push(video_id);
+                continue;
+            }
+            
+            // 只保留随导出一起带过来的文件，其余路径在新电脑上不存在
+            let rebase = |path: &str| -> Option<String> {
+                let relative = Path::new(path).strip_prefix(source_path?).ok()?;
+                let content = bundle.files.get(&bundle_file_key(relative))?;
+                let target = vault_path.join(relative);
+                ensure_inside_vault(&vault_path, &target).ok()?;
+                let written = target.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|_| fs::write(&target, content));
+                if let Err(e) = written {
+                    tracing::warn!("导入文件失败 {}: {}", target.display(), e);
+                    return None;
+                }
+                Some(target.to_string_lossy().to_string())
+            };
+            
+            record.transcript_file = record.transcript_file.as_deref().and_then(rebase);
+            record.translation_file = record.translation_file.as_deref().and_then(rebase);
+            record.metadata_file = record.metadata_file.as_deref().and_then(rebase);
+            record.subtitle_files = record.subtitle_files.iter()
+                .filter_map(|(format, path)| Some((format.clone(), rebase(path)?)))
+                .collect();
+            record.downloaded = false;
+            record.audio_purged = false;
+            record.audio_file = None;
+            record.audio_size_bytes = None;
+            record.thumbnail_file = None;
+            record.video_file = None;
+            record.chunked_transcription = None;
+            record.updated_at = get_current_timestamp();
+            
+            report.imported.push(video_id.clone());
+            vault.videos.insert(video_id, record);
+        }
+        report
+    })?;
+    
+    to_json(&report)
+}
+
+#[derive(Serialize)]
+struct VaultRelocationReport {
+    from: String,
+    to: String,
+    dry_run: bool,
+    move_files: bool,
+    /// 相对 vault 的路径，dry_run 时为将要复制的文件
+    files: Vec<String>,
+    total_bytes: u64,
+    /// 记录中改写为新位置的文件路径数
+    rewritten_paths: usize,
+}
+
+/// 把整个 vault 复制（move_files 为 true 时移动）到 new_base 下，并把记录中的绝对路径改为新位置。
+/// 先复制全部文件并逐个核对大小，确认无误后才删除原目录；dry_run 只报告将要复制的文件和改写的路径数。
+/// 完成后前端需要改用 new_base 作为 base_path
+#[tauri::command]
+fn relocate_vault(registry: State<'_, PipelineRegistry>, old_base: Option<String>, new_base: String, move_files: bool, dry_run: bool) -> Result<String, PipelineError> {
+    let from = resolve_vault_path(old_base);
+    let to = resolve_vault_path(Some(new_base));
+    if !from.join("vault.toml").is_file() {
+        return Err(PipelineError::NotFound(format!("原位置没有 vault: {}", from.display())));
+    }
+    if to.starts_with(&from) || from.starts_with(&to) {
+        return Err(PipelineError::InvalidInput(format!("新位置不能与原 vault 相同或互相包含: {}", to.display())));
+    }
+    if fs::read_dir(&to).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(PipelineError::InvalidInput(format!("新位置已存在且不为空: {}", to.display())));
+    }
+    if !registry.running.lock().unwrap().is_empty() {
+        return Err(PipelineError::AlreadyRunning("有流水线正在运行，请等待完成或取消后再迁移 vault".to_string()));
+    }
+    
+    to_json(&relocate_vault_files(&from, &to, move_files, dry_run)?)
+}
+
+fn relocate_vault_files(from: &PathBuf, to: &PathBuf, move_files: bool, dry_run: bool) -> Result<VaultRelocationReport, PipelineError> {
+    let lock = VaultLock::acquire(from)?;
+    let mut vault = load_vault(from)?;
+    let mut rewritten_paths = 0;
+    for record in vault.videos.values_mut() {
+        rewritten_paths += rebase_record_paths(record, from, to);
+        // 本地导入和录音的记录以 file:// 链接指向 vault 内的文件时一并改写
+        let Some(source) = local_file_path(&record.url) else { continue };
+        let Ok(relative) = source.strip_prefix(from) else { continue };
+        if let Ok(url) = reqwest::Url::from_file_path(to.join(relative)) {
+            record.url = url.to_string();
+            rewritten_paths += 1;
+        }
+    }
+    let mut files = Vec::new();
+    collect_vault_files(from, Path::new(""), &mut files)
+        .map_err(|e| PipelineError::VaultIo(format!("读取vault目录失败: {}", e)))?;
+    let total_bytes = files.iter().map(|(_, size)| size).sum();
+    
+    let report = VaultRelocationReport {
+        from: from.to_string_lossy().to_string(),
+        to: to.to_string_lossy().to_string(),
+        dry_run,
+        move_files,
+        files: files.iter().map(|(relative, _)| bundle_file_key(relative)).collect(),
+        total_bytes,
+        rewritten_paths,
+    };
+    if dry_run {
+        return Ok(report);
+    }
+    
+    let copy_and_verify = || -> Result<(), PipelineError> {
+        for (relative, _) in &files {
+            let target = to.join(relative);
+            target.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::copy(from.join(relative), &target))
+                .map_err(|e| PipelineError::VaultIo(format!("复制文件失败 {}: {}", relative.display(), e)))?;
+        }
+        // 迁移期间仍可能有日志写入，日志文件不核对大小
+        let mismatched = files.iter()
+            .filter(|(relative, _)| !relative.starts_with(LOG_DIR_NAME))
+            .find(|(relative, size)| fs::metadata(to.join(relative)).map(|metadata| metadata.len()).ok() != Some(*size));
+        if let Some((relative, _)) = mismatched {
+            return Err(PipelineError::VaultIo(format!("复制后的文件与原文件不一致: {}", relative.display())));
+        }
+        
+        // 核对完成后再按新路径重写 vault.toml。不用 save_vault，否则复制过去的 vault.toml.bak 会被旧配置覆盖
+        let content = toml::to_string_pretty(&vault)
+            .map_err(|e| PipelineError::VaultIo(format!("序列化vault配置失败: {}", e)))?;
+        write_file_atomically(&get_vault_config_path(to), &content)
+            .map_err(|e| PipelineError::VaultIo(format!("保存vault配置失败: {}", e)))
+    };
+    // 新位置在开始前为空，失败时整个删掉，原 vault 保持不变
+    if let Err(e) = copy_and_verify() {
+        if let Err(cleanup) = fs::remove_dir_all(to) {
+            tracing::warn!("清理未完成的 vault 副本失败 {}: {}", to.display(), cleanup);
+        }
+        return Err(e);
+    }
+    
+    if move_files {
+        drop(lock);
+        fs::remove_dir_all(from)
+            .map_err(|e| PipelineError::VaultIo(format!("vault 已复制到新位置，但删除原目录失败: {}", e)))?;
+    }
+    Ok(report)
+}
+
+/// 递归列出 vault 中需要迁移的文件及其大小，不含锁文件和写入中途留下的临时文件，不跟随符号链接
+fn collect_vault_files(vault_path: &Path, relative_dir: &Path, files: &mut Vec<(PathBuf, u64)>) -> std::io::Result<()> {
+    for entry in fs::read_dir(vault_path.join(relative_dir))? {
+        let entry = entry?;
+        let relative = relative_dir.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_vault_files(vault_path, &relative, files)?;
+        } else if file_type.is_file() && relative != Path::new("vault.lock") && !relative.to_string_lossy().ends_with(".tmp") {
+            files.push((relative, entry.metadata()?.len()));
+        }
+    }
+    Ok(())
+}
+
+/// vault_stats 中列出的最大视频数量
+const VAULT_STATS_LARGEST_COUNT: usize = 10;
+
+#[derive(Serialize)]
+struct VideoSize {
+    video_id: String,
+    title: Option<String>,
+    audio_size_bytes: u64,
+}
+
+#[derive(Serialize)]
+struct VaultStats {
+    video_count: usize,
+    downloaded: usize,
+    transcribed: usize,
+    summarized: usize,
+    /// 所有视频目录占用的字节数
+    total_bytes: u64,
+    /// 记录中有但磁盘上不存在的视频目录数量
+    missing_dirs: usize,
+    /// 按音频文件大小排列的最大视频
+    largest: Vec<VideoSize>,
+}
+
+/// 统计 vault 的视频数量、各状态的数量和磁盘占用，目录不存在的视频按 0 字节计
+#[tauri::command]
+fn vault_stats(base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    
+    let mut stats = VaultStats {
+        video_count: vault.videos.len(),
+        downloaded: 0,
+        transcribed: 0,
+        summarized: 0,
+        total_bytes: 0,
+        missing_dirs: 0,
+        largest: Vec::new(),
+    };
+    for record in vault.videos.values() {
+        stats.downloaded += usize::from(record.downloaded);
+        stats.transcribed += usize::from(record.transcribed);
+        stats.summarized += usize::from(record.summarized);
+        
+        let video_dir = get_video_dir_path(&vault_path, record);
+        if video_dir.is_dir() {
+            stats.total_bytes += directory_size(&video_dir);
+        } else {
+            stats.missing_dirs += 1;
+        }
+        
+        // 旧记录没有保存音频大小时按磁盘上的文件计算
+        let audio_size = record.audio_size_bytes.or_else(|| record.audio_file.as_deref().and_then(get_file_size));
+        if let Some(audio_size_bytes) = audio_size {
+            stats.largest.push(VideoSize { video_id: record.id.clone(), title: record.title.clone(), audio_size_bytes });
+        }
+    }
+    stats.largest.sort_by_key(|video| std::cmp::Reverse(video.audio_size_bytes));
+    stats.largest.truncate(VAULT_STATS_LARGEST_COUNT);
+    
+    to_json(&stats)
+}
+
+/// 检查每条记录引用的目录和文件是否存在，只报告不修改
+#[tauri::command]
+fn verify_vault(base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    
+    let issues = vault.videos.values()
+        .flat_map(|record| inspect_record(&vault_path, record))
+        .collect();
+    
+    to_json(&VaultReport { checked: vault.videos.len(), issues })
+}
+
+/// 按记录中的转录文本重新写出丢失的转录文件，写入失败时返回 false
+fn restore_transcript_file(path: &Path, content: &str) -> bool {
+    let written = path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| write_file_atomically(path, content));
+    if let Err(e) = &written {
+        tracing::warn!("写回转录文件失败 {}: {}", path.display(), e);
+    }
+    written.is_ok()
+}
+
+/// 重置缺少对应文件的状态标记，下次运行流水线时会重新生成；转录文件丢失但记录中有转录文本时直接写回。
+/// 返回修复前发现的问题
+#[tauri::command]
+fn repair_vault(base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    
+    let report = update_vault(&vault_path, |vault| {
+        let mut issues = Vec::new();
+        for record in vault.videos.values_mut() {
+            let record_issues = inspect_record(&vault_path, record);
+            for issue in &record_issues {
+                match issue.missing {
+                    MissingItem::VideoDir => {}
+                    MissingItem::AudioFile => {
+                        record.downloaded = false;
+                        record.audio_file = None;
+                        record.audio_size_bytes = None;
+                    }
+                    MissingItem::TranscriptFile => {
+                        // 记录中保存着转录文本时直接写回文件；无法写回时重置转录和依赖它的总结，由流水线重新生成
+                        let restored = match (issue.path.as_deref(), record.transcript_content.as_deref()) {
+                            (Some(path), Some(content)) => restore_transcript_file(Path::new(path), content),
+                            _ => false,
+                        };
+                        if !restored {
+                            clear_transcription(record);
+                        }
+                    }
+                    MissingItem::SubtitleFile => {
+                        record.subtitle_files.retain(|_, path| Some(path.as_str()) != issue.path.as_deref());
+                    }
+                }
+            }
+            if !record_issues.is_empty() {
+                record.updated_at = get_current_timestamp();
+            }
+            issues.extend(record_issues);
+        }
+        VaultReport { checked: vault.videos.len(), issues }
+    })?;
+    
+    to_json(&report)
+}
+
+#[derive(Serialize)]
+struct LayoutMigrationSkip {
+    video_id: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct LayoutMigrationReport {
+    /// 已移动的视频 id
+    moved: Vec<String>,
+    skipped: Vec<LayoutMigrationSkip>,
+}
+
+/// 按 config.toml 中的 dir_layout 移动已有的视频目录，只移动不删除；
+/// 正在处理的视频、目标目录已存在或缺少元数据的记录会被跳过
+#[tauri::command]
+fn migrate_vault_layout(registry: State<'_, PipelineRegistry>, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let layout = load_vault_config(&vault_path)?.dir_layout.unwrap_or_default();
+    
+    let report = update_vault(&vault_path, |vault| {
+        let mut report = LayoutMigrationReport { moved: Vec::new(), skipped: Vec::new() };
+        let running = registry.running.lock().unwrap();
+        for (video_id, record) in vault.videos.iter_mut() {
+            let mut skip = |reason: &str| report.skipped.push(LayoutMigrationSkip {
+                video_id: video_id.clone(),
+                reason: reason.to_string(),
+            });
+            if running.contains_key(video_id) {
+                skip("该视频正在处理中");
+                continue;
+            }
+            
+            // 旧记录没有发布日期和上传者，从保存的元数据中补齐
+            if record.upload_date.is_none() || record.uploader.is_none() {
+                let metadata = record.metadata_file.as_deref()
+                    .and_then(|path| fs::read(path).ok())
+                    .and_then(|body| serde_json::from_slice::<serde_json::Value>(&body).ok());
+                if let Some(metadata) = metadata {
+                    let text_field = |key: &str| metadata[key].as_str().map(|value| value.trim().to_string());
+                    record.upload_date = record.upload_date.take().or_else(|| text_field("upload_date"));
+                    record.uploader = record.uploader.take().or_else(|| text_field("uploader"));
+                }
+            }
+            
+            let target = layout_video_dir(layout, record);
+            if layout != VideoDirLayout::Flat && target.is_none() {
+                skip("缺少发布日期或上传者信息");
+                continue;
+            }
+            if target == record.video_dir {
+                continue;
+            }
+            match relocate_video_dir(&vault_path, record, target) {
+                Ok(()) => {
+                    record.updated_at = get_current_timestamp();
+                    report.moved.push(video_id.clone());
+                }
+                Err(e) => skip(&e.to_string()),
+            }
+        }
+        report
+    })?;
+    
+    to_json(&report)
+}
+
+/// vault 中没有任何记录引用的目录，通常是中途崩溃的流水线留下的
+#[derive(Serialize)]
+struct OrphanDir {
+    /// 相对 vault 的路径
+    path: String,
+    size_bytes: u64,
+}
+
+#[derive(Serialize)]
+struct OrphanPruneSkip {
+    path: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct OrphanPruneReport {
+    removed: Vec<String>,
+    freed_bytes: u64,
+    skipped: Vec<OrphanPruneSkip>,
+}
+
+/// 列出 vault 下没有被任何记录引用的目录及其大小，不做任何修改
+#[tauri::command]
+fn find_orphans(registry: State<'_, PipelineRegistry>, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let running = registry.running.lock().unwrap().keys().cloned().collect();
+    
+    to_json(&find_orphan_dirs(&vault_path, &vault, &running)?)
+}
+
+/// 删除用户确认过的孤立目录。删除前持锁重新检查，期间已被记录引用、正在处理或不在 vault 内的目录会被跳过
+#[tauri::command]
+fn prune_orphans(registry: State<'_, PipelineRegistry>, base_path: Option<String>, paths: Vec<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let _lock = VaultLock::acquire(&vault_path)?;
+    let vault = load_vault(&vault_path)?;
+    let running = registry.running.lock().unwrap().keys().cloned().collect();
+    let orphans = find_orphan_dirs(&vault_path, &vault, &running)?;
+    
+    let mut report = OrphanPruneReport { removed: Vec::new(), freed_bytes: 0, skipped: Vec::new() };
+    for path in paths {
+        let Some(orphan) = orphans.iter().find(|orphan| orphan.path == path) else {
+            report.skipped.push(OrphanPruneSkip { path, reason: "不是孤立目录".to_string() });
+            continue;
+        };
+        let target = vault_path.join(&orphan.path);
+        if let Err(e) = ensure_inside_vault(&vault_path, &target) {
+            report.skipped.push(OrphanPruneSkip { path, reason: e.to_string() });
+            continue;
+        }
+        match fs::remove_dir_all(&target) {
+            Ok(()) => {
+                report.freed_bytes += orphan.size_bytes;
+                report.removed.push(path);
+            }
+            Err(e) => report.skipped.push(OrphanPruneSkip { path, reason: format!("删除目录失败: {}", e) }),
+        }
+    }
+    
+    to_json(&report)
+}
+
+/// 从 vault 根目录开始查找孤立目录。只看目录，vault.toml、config.toml 等文件不会被列出；
+/// 按日期或上传者分组的目录中如果有被引用的视频目录，则继续检查其中的子目录
+fn find_orphan_dirs(vault_path: &Path, vault: &Vault, running: &HashSet<String>) -> Result<Vec<OrphanDir>, PipelineError> {
+    let referenced: Vec<PathBuf> = vault.videos.values()
+        .map(|record| PathBuf::from(record.video_dir.as_deref().unwrap_or(&record.id)))
+        .collect();
+    
+    let mut orphans = Vec::new();
+    if vault_path.is_dir() {
+        collect_orphan_dirs(vault_path, Path::new(""), &referenced, running, &mut orphans)?;
+    }
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(orphans)
+}
+
+fn collect_orphan_dirs(vault_path: &Path, relative_dir: &Path, referenced: &[PathBuf], running: &HashSet<String>, orphans: &mut Vec<OrphanDir>) -> Result<(), PipelineError> {
+    let entries = fs::read_dir(vault_path.join(relative_dir))
+        .map_err(|e| PipelineError::VaultIo(format!("读取vault目录失败: {}", e)))?;
+    
+    for entry in entries.flatten() {
+        // 不跟随符号链接，隐藏目录也不处理
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_dir || name.starts_with('.') || running.contains(&name) {
+            continue;
+        }
+        
+        let relative = relative_dir.join(&name);
+        if referenced.contains(&relative) {
+            continue;
+        }
+        if referenced.iter().any(|dir| dir.starts_with(&relative)) {
+            collect_orphan_dirs(vault_path, &relative, referenced, running, orphans)?;
+            continue;
+        }
+        orphans.push(OrphanDir {
+            path: relative.components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            size_bytes: directory_size(&entry.path()),
+        });
+    }
+    Ok(())
+}
+
+/// 目录下所有文件的总大小，不跟随符号链接，读取失败的条目忽略
+fn directory_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries.flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn inspect_record(vault_path: &Path, record: &VideoRecord) -> Vec<VaultIssue> {
+    let issue = |missing, path: Option<&str>| VaultIssue {
+        video_id: record.id.clone(),
+        missing,
+        path: path.map(|path| path.to_string()),
+    };
+    let file_missing = |path: &str| !Path::new(path).is_file();
+    let mut issues = Vec::new();
+    
+    let video_dir = get_video_dir_path(vault_path, record);
+    if !video_dir.is_dir() {
+        issues.push(issue(MissingItem::VideoDir, Some(&video_dir.to_string_lossy())));
+    }
+    
+    if record.downloaded && !record.audio_purged {
+        match record.audio_file.as_deref() {
+            Some(path) if !file_missing(path) => {}
+            path => issues.push(issue(MissingItem::AudioFile, path)),
+        }
+    }
+    
+    if record.transcribed {
+        match record.transcript_file.as_deref() {
+            Some(path) if !file_missing(path) => {}
+            path => issues.push(issue(MissingItem::TranscriptFile, path)),
+        }
+    }
+    
+    for path in record.subtitle_files.values() {
+        if file_missing(path) {
+            issues.push(issue(MissingItem::SubtitleFile, Some(path)));
+        }
+    }
+    
+    issues
+}
+
+/// 继续处理已有记录时的参数：options 中未指定的下载、转录设置沿用记录中保存的值
+fn resume_options(record: &VideoRecord, mut options: PipelineOptions) -> PipelineOptions {
+    // 已下载的音频格式和已使用的转录模型保持不变，分段转录的进度才能继续使用
+    options.audio_format = options.audio_format.or_else(|| record.audio_format.clone());
+    options.whisper_model = options.whisper_model.or_else(|| record.whisper_model.clone());
+    // 片段记录的 id 包含时间段，需要带上同样的时间段才能找到原记录
+    if let Some(range) = record.time_range {
+        options.start_time = Some(format_clock_time(range.start));
+        options.end_time = Some(format_clock_time(range.end));
+    }
+    options
+}
+
+/// 记录无法继续处理的原因：没有链接，或者还需要转录而音频已丢失且无法重新获取
+fn unresumable_reason(record: &VideoRecord) -> Option<String> {
+    if record.url.trim().is_empty() {
+        return Some("记录中没有视频链接".to_string());
+    }
+    let audio_missing = record.downloaded && !record.transcribed && !record.audio_purged
+        && !record.audio_file.as_deref().is_some_and(|path| Path::new(path).is_file());
+    if audio_missing {
+        return Some("音频文件已丢失，请使用 redownload_video 重新下载".to_string());
+    }
+    // 本地导入的文件需要重新复制时，原文件必须还在
+    let needs_source = !record.downloaded || (!record.transcribed && record.audio_purged);
+    match local_file_path(&record.url) {
+        Some(source) if needs_source && !source.is_file() => Some(format!("本地文件已不存在: {}", source.display())),
+        _ => None,
+    }
+}
+
+/// `resume_all` 登记在 PipelineRegistry 中的 id，cancel_pipeline 此 id 后尚未开始的记录不再处理
+const RESUME_ALL_ID: &str = "resume-all";
+
+/// 依次从第一个未完成的步骤继续处理 vault 中所有未完成的记录，例如安装好 whisper 之后恢复之前失败的视频。
+/// 转录仍经过 TranscriptionQueue 排队；缺少链接或音频无法找回的记录跳过。返回每条记录的处理结果
+#[tauri::command]
+async fn resume_all(app: AppHandle, registry: State<'_, PipelineRegistry>, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let options = options.unwrap_or_default().with_api(api_key, api_provider);
+    let vault_path = resolve_vault_path(base_path);
+    let mut records: Vec<VideoRecord> = load_vault(&vault_path)?.videos.into_values()
+        .filter(|record| !record.downloaded || !record.transcribed || !record.summarized)
+        .collect();
+    records.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    
+    let progress = ProgressEmitter { app: app.clone(), video_id: RESUME_ALL_ID.to_string() };
+    let running = registry.start(RESUME_ALL_ID)?;
+    let total = records.len();
+    progress.emit("resume", Some(0.0), &format!("共 {} 个未完成的视频", total));
+    
+    let mut results = Vec::with_capacity(total);
+    for (index, record) in records.into_iter().enumerate() {
+        let mut trace = PipelineTrace::default();
+        let (status, error) = if running.token.is_cancelled() {
+            (BatchItemStatus::Cancelled, None)
+        } else if let Some(reason) = unresumable_reason(&record) {
+            progress.emit("resume", None, &format!("跳过 {}: {}", record.id, reason));
+            trace.warnings.push(reason);
+            (BatchItemStatus::Skipped, None)
+        } else {
+            let record_options = resume_options(&record, options.clone());
+            match run_pipeline(&app, &registry, &record.url, &vault_path, &record_options, &mut trace).await {
+                Ok(_) => (BatchItemStatus::Done, None),
+                Err(e) => {
+                    progress.emit("resume", None, &format!("处理失败 {}: {}", record.id, e));
+                    (BatchItemStatus::Failed, Some(e))
+                }
+            }
+        };
+        progress.emit("resume", Some((index + 1) as f32 / total as f32 * 100.0), &format!("已处理 {}/{}", index + 1, total));
+        results.push(BatchItemResult { url: record.url, video_id: record.id, status, stages: trace.stages, warnings: trace.warnings, error });
+    }
+    
+    to_json(&results)
+}
+
+/// 从记录中第一个未完成的步骤继续执行。下载、转录参数优先使用 overrides，未指定的沿用记录中保存的设置
+#[tauri::command]
+async fn retry_video(app: AppHandle, registry: State<'_, PipelineRegistry>, video_id: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, overrides: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    
+    let (stage, stage_label) = if !record.downloaded {
+        ("download", "下载")
+    } else if !record.transcribed {
+        ("transcribe", "转录")
+    } else if !record.summarized {
+        ("summarize", "总结")
+    } else {
+        return Err(PipelineError::InvalidInput("该视频的所有步骤都已完成，无需重试".to_string()));
+    };
+    
+    let options = resume_options(record, overrides.unwrap_or_default().with_api(api_key, api_provider));
+    
+    // 流水线按记录中的完成标记跳过之前的步骤，从第一个未完成的步骤开始执行
+    let progress = ProgressEmitter { app: app.clone(), video_id: video_id.clone() };
+    progress.emit(stage, None, &format!("从{}步骤开始重试", stage_label));
+    
+    let mut trace = PipelineTrace::default();
+    let record = run_pipeline(&app, &registry, &record.url, &vault_path, &options, &mut trace).await?;
+    to_json(&PipelineResult::new(record, trace))
+}
+
+/// 用新的模型或语言重新转录已下载的视频，并重新生成总结。新设置通过 options 的
+/// `whisper_model`、`language` 等字段传入，未指定的沿用记录中保存的值
+#[tauri::command]
+async fn retranscribe_video(app: AppHandle, registry: State<'_, PipelineRegistry>, video_id: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    
+    // 音频被主动删除的记录由流水线重新下载
+    let audio_exists = record.downloaded && record.audio_file.as_deref().is_some_and(|path| Path::new(path).is_file());
+    if !audio_exists && !record.audio_purged {
+        return Err(PipelineError::NotFound("音频文件不存在，无法重新转录，请删除该视频后重新下载".to_string()));
+    }
+    
+    let mut options = resume_options(record, options.unwrap_or_default().with_api(api_key, api_provider));
+    options.force_retranscribe = true;
+    
+    let mut trace = PipelineTrace::default();
+    let record = run_pipeline(&app, &registry, &record.url, &vault_path, &options, &mut trace).await?;
+    to_json(&PipelineResult::new(record, trace))
+}
+
+/// 用新的音频格式或质量重新下载视频，并重新转录和总结；备注和标签保持不变
+#[tauri::command]
+async fn redownload_video(app: AppHandle, registry: State<'_, PipelineRegistry>, video_id: String, base_path: Option<String>, audio_format: Option<String>, audio_quality: Option<String>, options: Option<PipelineOptions>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    if local_file_path(&record.url).is_some() {
+        return Err(PipelineError::InvalidInput("本地导入的文件无法重新下载".to_string()));
+    }
+    
+    let mut options = options.unwrap_or_default();
+    if audio_format.is_some() {
+        options.audio_format = audio_format;
+    }
+    if audio_quality.is_some() {
+        options.audio_quality = audio_quality;
+    }
+    // 未指定时沿用上次下载的格式选择器
+    options.format_selector = options.format_selector.or_else(|| record.format_selector.clone());
+    let mut options = resume_options(record, options);
+    // 转录结果来自旧音频，一并重新生成
+    options.force_redownload = true;
+    options.force_retranscribe = true;
+    
+    let mut trace = PipelineTrace::default();
+    let record = run_pipeline(&app, &registry, &record.url, &vault_path, &options, &mut trace).await?;
+    to_json(&PipelineResult::new(record, trace))
+}
+
+/// 各服务、模型的输入价格，单位为美元 / 百万 token，价格变动时只需修改这里
+const SUMMARY_INPUT_PRICES: [(&str, &str, f64); 9] = [
+    ("openai", "gpt-3.5-turbo", 0.5),
+    ("openai", "gpt-4o", 2.5),
+    ("openai", "gpt-4o-mini", 0.15),
+    ("deepseek", "deepseek-chat", 0.27),
+    ("anthropic", "claude-3-5-sonnet-latest", 3.0),
+    ("anthropic", "claude-3-5-haiku-latest", 0.8),
+    ("gemini", "gemini-1.5-flash", 0.075),
+    ("gemini", "gemini-1.5-pro", 1.25),
+    ("gemini", "gemini-2.0-flash", 0.1),
+];
+
+/// 总结费用的粗略估算，只计算输入部分
+#[derive(Serialize)]
+struct SummaryCostEstimate {
+    video_id: String,
+    provider: String,
+    model: String,
+    /// 转录文本和提示词的估算 token 数
+    input_tokens: usize,
+    /// 价格表中没有该模型（如自建服务）时为空
+    usd_per_million_tokens: Option<f64>,
+    estimated_usd: Option<f64>,
+}
+
+/// 粗略估算 token 数：中日韩等文字每字约 1 个 token，其他语言每词约 4/3 个 token
+fn estimate_tokens(text: &str) -> usize {
+    let (unspaced_chars, words) = text.split_whitespace()
+        .fold((0, 0), |(unspaced_chars, words), token| {
+            let unspaced = token.chars().filter(|c| is_unspaced_script_char(*c)).count();
+            let has_other_text = token.chars().any(|c| c.is_alphanumeric() && !is_unspaced_script_char(c));
+            (unspaced_chars + unspaced, words + usize::from(has_other_text))
+        });
+    unspaced_chars + (words * 4).div_ceil(3)
+}
+
+/// 估算用指定服务和模型总结某个视频的费用，不调用任何 API
+#[tauri::command]
+fn estimate_summary_cost(video_id: String, provider: Option<String>, model: Option<String>, base_path: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    let transcript = record.transcript_content.as_deref()
+        .ok_or_else(|| PipelineError::InvalidInput("该视频还没有转录内容，无法估算总结费用".to_string()))?;
+    
+    let mut options = PipelineOptions::default().with_api(None, provider);
+    options.summary_model = model;
+    let options = options.with_defaults(&load_vault_config(&vault_path)?);
+    let summary_options = SummaryOptions::from_pipeline_options(&options, None)?;
+    summary_options.validate()?;
+    
+    let provider = summary_options.provider.name().to_string();
+    let model = summary_options.model().to_string();
+    let input_tokens = estimate_tokens(transcript) + estimate_tokens(&summary_options.system_prompt);
+    let usd_per_million_tokens = SUMMARY_INPUT_PRICES.iter()
+        .find(|(known_provider, known_model, _)| *known_provider == provider && *known_model == model)
+        .map(|(_, _, price)| *price);
+    to_json(&SummaryCostEstimate {
+        video_id,
+        provider,
+        model,
+        input_tokens,
+        usd_per_million_tokens,
+        estimated_usd: usd_per_million_tokens.map(|price| input_tokens as f64 / 1_000_000.0 * price),
+    })
+}
+
+/// 检查 API 密钥时等待响应的秒数
+const API_KEY_CHECK_TIMEOUT_SECONDS: u64 = 15;
+
+/// 用密钥请求服务的模型列表，检查密钥是否有效：有效返回 true，被拒绝（401/403）返回 false，
+/// 连不上服务或服务返回其他错误时返回 Network 错误。密钥只用于这次请求，不会保存
+#[tauri::command]
+async fn validate_api_key(api_key: String, api_provider: Option<String>, options: Option<PipelineOptions>) -> Result<bool, PipelineError> {
+    let options = options.unwrap_or_default().with_api(Some(api_key.trim().to_string()), api_provider);
+    let provider = ApiProvider::from_options(&options)?;
+    let api_key = options.api_key.as_deref().unwrap_or_default();
+    if api_key.is_empty() && provider.requires_api_key() {
+        return Err(PipelineError::InvalidInput("请输入 API 密钥".to_string()));
+    }
+    let proxy = DownloadOptions::from_pipeline_options(&options).proxy;
+    if let Some(proxy) = &proxy {
+        validate_proxy_url(proxy)?;
+    }
+    
+    let client = build_http_client(proxy.as_deref())?;
+    let request = client.get(provider.models_endpoint())
+        .timeout(std::time::Duration::from_secs(API_KEY_CHECK_TIMEOUT_SECONDS));
+    let request = match provider {
+        ApiProvider::Anthropic => request
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_API_VERSION),
+        ApiProvider::Gemini => request.query(&[("key", api_key)]),
+        ApiProvider::OpenAI | ApiProvider::DeepSeek | ApiProvider::Custom { .. } if !api_key.is_empty() => {
+            request.header("Authorization", format!("Bearer {}", api_key))
+        }
+        ApiProvider::OpenAI | ApiProvider::DeepSeek | ApiProvider::Custom { .. } => request,
+    };
+    // reqwest 的错误信息会带上完整 URL，Gemini 的密钥在查询参数里，不能原样返回
+    let response = request.send().await
+        .map_err(|e| PipelineError::Network(format!("无法连接 {}: {}", provider.name(), e.without_url())))?;
+    
+    let status = response.status();
+    // Gemini 对无效密钥返回 400 API_KEY_INVALID
+    let gemini_invalid_key = matches!(provider, ApiProvider::Gemini) && status == reqwest::StatusCode::BAD_REQUEST;
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN || gemini_invalid_key {
+        return Ok(false);
+    }
+    if !status.is_success() {
+        return Err(PipelineError::Network(format!("{} 返回了错误，状态码: {}", provider.name(), status)));
+    }
+    Ok(true)
+}
+
+/// 用已保存的转录内容重新生成总结，不重新下载和转录，便于尝试不同的模型或提示词
+#[tauri::command]
+async fn resummarize_video(registry: State<'_, PipelineRegistry>, video_id: String, base_path: Option<String>, api_key: Option<String>, api_provider: Option<String>, prompt: Option<String>, model: Option<String>) -> Result<String, PipelineError> {
+    let vault_path = resolve_vault_path(base_path);
+    
+    let mut options = PipelineOptions::default().with_api(api_key, api_provider);
+    options.summary_prompt = prompt;
+    options.summary_model = model;
+    let options = options.with_defaults(&load_vault_config(&vault_path)?);
+    let proxy = DownloadOptions::from_pipeline_options(&options).proxy;
+    if let Some(proxy) = &proxy {
+        validate_proxy_url(proxy)?;
+    }
+    let summary_options = SummaryOptions::from_pipeline_options(&options, proxy)?;
+    summary_options.validate()?;
+    // 用户主动重新总结时，简单总结会覆盖掉原有的总结，只在明确要求离线时才使用
+    if !summary_options.offline && summary_options.api_key.is_none() && summary_options.provider.requires_api_key() {
+        return Err(PipelineError::InvalidInput("未配置 API 密钥，无法重新总结".to_string()));
+    }
+    
+    // 与流水线共用登记，避免同一视频正在处理时覆盖结果
+    let _running = registry.start(&video_id)?;
+    
+    let vault = load_vault(&vault_path)?;
+    let record = vault.videos.get(&video_id)
+        .ok_or_else(|| PipelineError::NotFound(format!("未找到视频记录: {}", video_id)))?;
+    let transcript = record.transcript_content.as_deref()
+        .ok_or_else(|| PipelineError::InvalidInput("该视频还没有转录内容，无法重新总结".to_string()))?;
+    
+    let summary = summarize_transcript_content(transcript, &summary_options).await?;
+    let chapter_summaries = if summary.provider == SIMPLE_SUMMARY_PROVIDER {
+        // 接口暂时不可用而回退到简单总结时保留原有记录
+        if !summary_options.offline {
+            return Err(PipelineError::Network("总结接口暂时不可用，已保留原有总结".to_string()));
+        }
+        None
+    } else {
+        summarize_chapters(record, &summary_options).await.unwrap_or_else(|e| {
+            tracing::warn!("生成章节总结失败: {}", e);
+            None
+        })
+    };
+    
+    let updated = update_vault_async(&vault_path, |vault| {
+        vault.videos.get_mut(&video_id).map(|record| {
+            record.summarized = true;
+            record.summary_content = Some(summary.text);
+            record.word_count = Some(summary.word_count);
+            record.reading_minutes = Some(summary.reading_minutes);
+            record.summary_provider = Some(summary.provider);
+            record.summary_model = summary.model;
+            record.summary_style = summary.style;
+            record.keywords = Some(summary.keywords);
+            record.summaries = summary.summaries;
+            record.chapter_summaries = chapter_summaries;
+            record.updated_at = get_current_timestamp();
+            record.clone()
+        })
+    }).await?;
+    
+    match updated {
+        Some(record) => to_json(&record),
+        None => Err(PipelineError::NotFound(format!("未找到视频记录: {}", video_id))),
+    }
+}
+
+/// 查找音频内容相同且已转录的其他记录。只有模型、语言一致且具备所需的字幕和分段时才复用，
+/// 避免用户指定的转录参数被忽略
+fn find_duplicate_transcription(vault: &Vault, record: &VideoRecord, options: &TranscribeOptions) -> Option<VideoRecord> {
+    let hash = record.audio_sha256.as_deref()?;
+    vault.videos.values()
+        .filter(|other| other.id != record.id && other.transcribed)
+        .filter(|other| other.audio_sha256.as_deref() == Some(hash))
+        .filter(|other| other.transcript_content.is_some())
+        .filter(|other| other.whisper_model.as_deref() == Some(options.model.as_str()))
+        .filter(|other| options.language.is_none() || other.transcribe_language == options.language)
+        .filter(|other| options.subtitle_formats.iter().all(|format| other.subtitle_files.get(format).is_some_and(|path| Path::new(path).is_file())))
+        .find(|other| !options.include_segments || other.segments.is_some())
+        .cloned()
+}
+
+/// 把另一条记录的转录结果复制到本视频的转录输出目录，代替重新运行 whisper
+fn copy_transcription(source: &VideoRecord, audio_file_path: &str, options: &TranscribeOptions) -> Result<TranscriptionOutput, PipelineError> {
+    let content = source.transcript_content.clone().unwrap_or_default();
+    
+    let transcript_path = options.output_file(audio_file_path, "txt");
+    fs::write(&transcript_path, format!("{}\n", content))
+        .map_err(|e| PipelineError::TranscriptionFailed(format!("写入转录文件失败: {}", e)))?;
+    
+    let mut subtitle_files = HashMap::new();
+    for format in &options.subtitle_formats {
+        let source_file = source.subtitle_files.get(format)
+            .ok_or_else(|| PipelineError::TranscriptionFailed(format!("未找到 {} 字幕文件", format)))?;
+        let target = options.output_file(audio_file_path, format);
+        fs::copy(source_file, &target)
+            .map_err(|e| PipelineError::TranscriptionFailed(format!("复制 {} 字幕文件失败: {}", format, e)))?;
+        subtitle_files.insert(format.clone(), target.to_string_lossy().to_string());
+    }
+    
+    Ok(TranscriptionOutput {
+        content,
+        transcript_file: transcript_path.to_string_lossy().to_string(),
+        subtitle_files,
+        segments: if options.include_segments { source.segments.clone() } else { None },
+        detected_language: source.transcribe_language.clone(),
+        warnings: Vec::new(),
+    })
+}
+
+/// 转录完成后删除音频文件，并标记音频已删除；删除失败时记录保持不变
+fn purge_audio_file(record: &mut VideoRecord) -> std::io::Result<()> {
+    if let Some(audio_file) = &record.audio_file {
+        fs::remove_file(audio_file)?;
+    }
+    record.audio_file = None;
+    record.audio_purged = true;
+    record.updated_at = get_current_timestamp();
+    Ok(())
+}
+
+/// 清空转录和总结结果，并删除旧的转录和字幕文件
+fn clear_transcription(record: &mut VideoRecord) {
+    let stale_files = record.transcript_file.take().into_iter()
+        .chain(record.translation_file.take())
+        .chain(record.subtitle_files.drain().map(|(_, path)| path));
+    for path in stale_files {
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("删除旧转录文件失败 {}: {}", path, e);
+            }
+        }
+    }
+    
+    record.transcribed = false;
+    record.summarized = false;
+    record.transcript_content = None;
+    record.translation_content = None;
+    record.summary_content = None;
+    record.segments = None;
+    record.chunked_transcription = None;
+    record.word_count = None;
+    record.reading_minutes = None;
+    record.summary_provider = None;
+    record.summary_model = None;
+    record.summary_style = None;
+    record.keywords = None;
+    record.summaries = None;
+    record.chapter_summaries = None;
+    record.whisper_model = None;
+    record.transcribe_language = None;
+}
+
+/// 确认目标路径位于 vault 目录之内，防止通过构造的 id 删除 vault 以外的文件
+fn ensure_inside_vault(vault_path: &Path, target: &Path) -> Result<(), PipelineError> {
+    let is_nested_child = target.strip_prefix(vault_path).is_ok_and(|relative| {
+        relative.components().next().is_some()
+            && relative.components().all(|component| matches!(component, std::path::Component::Normal(_)))
+    });
+    
+    // 目录已存在时再比较真实路径，排除符号链接指向外部的情况
+    let resolves_inside = match (vault_path.canonicalize(), target.canonicalize()) {
+        (Ok(vault), Ok(target)) => target.starts_with(&vault) && target != vault,
+        _ => true,
+    };
+    
+    if is_nested_child && resolves_inside {
+        Ok(())
+    } else {
+        Err(PipelineError::InvalidInput(format!("拒绝操作 vault 目录之外的路径: {}", target.display())))
+    }
+}
+
+/// 用 yt-dlp 展开播放列表，返回每个条目的视频链接
+async fn list_playlist_entries(url: &str, options: &DownloadOptions) -> Result<Vec<String>, PipelineError> {
+    let mut command = options.yt_dlp_command();
+    command
+        .arg("--flat-playlist")
+        .arg("--print").arg("%(id)s\t%(url)s");
+    options.apply_network_args(&mut command);
+    command.arg(url);
+    let output = tokio::time::timeout(options.timeout, command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| PipelineError::Timeout(format!("获取播放列表超过 {} 分钟未完成", options.timeout.as_secs() / 60)))?
+        .map_err(|e| yt_dlp_spawn_error(&e))?;
+    
+    if !output.status.success() {
+        let stderr = decode_tool_output(&output.stderr);
+        return Err(download_failure(format!("无法获取播放列表: {}", stderr.trim()), &stderr));
+    }
+    
+    let entries: Vec<String> = decode_tool_output(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (id, entry_url) = line.trim().split_once('\t')?;
+            if entry_url.starts_with("http") {
+                Some(entry_url.to_string())
+            } else if !id.is_empty() && id != "NA" {
+                // 部分站点在 flat 模式下不提供完整链接，按 YouTube 的规则拼接
+                Some(format!("https://www.youtube.com/watch?v={}", id))
+            } else {
+                None
+            }
+        })
+        .collect();
+    
+    if entries.is_empty() {
+        return Err(PipelineError::DownloadFailed("播放列表为空或无法解析".to_string()));
+    }
+    
+    Ok(entries)
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum StageName {
+    Download,
+    Transcribe,
+    Summarize,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum StageStatus {
+    /// 之前已完成，本次跳过
+    Skipped,
+    /// 预览时表示实际运行会执行该步骤
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+/// 流水线中一个步骤的结果，前端根据 name 和 status 显示本地化的文字
+#[derive(Serialize, Clone)]
+struct PipelineStage {
+    name: StageName,
+    status: StageStatus,
+    detail: Option<String>,
+}
+
+impl PipelineStage {
+    fn new(name: StageName, status: StageStatus, detail: Option<String>) -> Self {
+        PipelineStage { name, status, detail }
+    }
+}
+
+/// 处理单个视频的返回值：记录本身、各步骤的结果以及不影响结果的警告
+#[derive(Serialize)]
+struct PipelineResult {
+    record: VideoRecord,
+    stages: Vec<PipelineStage>,
+    /// 如缩略图获取失败、使用了简单总结等
+    warnings: Vec<String>,
+}
+
+impl PipelineResult {
+    fn new(record: VideoRecord, trace: PipelineTrace) -> Self {
+        PipelineResult { record, stages: trace.stages, warnings: trace.warnings }
+    }
+}
+
+/// 流水线执行过程中记录的步骤结果和警告，出错时也保留已完成的部分
+#[derive(Default)]
+struct PipelineTrace {
+    stages: Vec<PipelineStage>,
+    warnings: Vec<String>,
+    /// 确定视频 id 后记录下来，流水线失败时也能知道是哪个视频
+    video_id: Option<String>,
+}
+
+/// 流水线结束时发送给 webhook 的内容
+#[derive(Serialize)]
+struct WebhookPayload {
+    video_id: Option<String>,
+    title: Option<String>,
+    /// success、failed 或 cancelled
+    status: &'static str,
+    summary: Option<String>,
+    error: Option<String>,
+}
+
+const WEBHOOK_TIMEOUT_SECONDS: u64 = 10;
+
+/// 预览流水线的返回值：视频信息和各步骤是否会执行
+#[derive(Serialize)]
+struct PipelinePlan {
+    video_id: String,
+    title: String,
+    duration_seconds: Option<u64>,
+    /// vault 中是否已有该视频的记录
+    existing: bool,
+    stages: Vec<PipelineStage>,
+}
+
+/// 把最后一个正在执行的步骤标记为完成
+fn finish_stage(stages: &mut [PipelineStage], detail: Option<String>) {
+    if let Some(stage) = stages.last_mut().filter(|stage| stage.status == StageStatus::Running) {
+        stage.status = StageStatus::Done;
+        stage.detail = detail;
+    }
+}
+
+/// 对单个视频执行 下载 -> 转录 -> 总结，每完成一步都会保存到 vault；
+/// 各步骤的结果写入 `stages`，出错时正在执行的步骤标记为 Failed
+async fn run_pipeline(app: &AppHandle, registry: &PipelineRegistry, url: &str, vault_path: &PathBuf, options: &PipelineOptions, trace: &mut PipelineTrace) -> Result<VideoRecord, PipelineError> {
+    use_log_vault(vault_path);
+    let result = run_pipeline_steps(app, registry, url, vault_path, options, trace).await;
+    if let Err(e) = &result {
+        for stage in trace.stages.iter_mut().filter(|stage| stage.status == StageStatus::Running) {
+            stage.status = StageStatus::Failed;
+            stage.detail = Some(e.to_string());
+        }
+    }
+    notify_webhook(vault_path, options, trace, &result).await;
+    result
+}
+
+/// 把流水线结果发送到 webhook；通知失败只记录日志，不影响流水线结果
+async fn notify_webhook(vault_path: &PathBuf, options: &PipelineOptions, trace: &PipelineTrace, result: &Result<VideoRecord, PipelineError>) {
+    let options = options.clone().with_defaults(&load_vault_config(vault_path).unwrap_or_default());
+    // 地址不合法时流水线已在开始前报错
+    let Some(webhook_url) = options.webhook_url.as_deref().filter(|url| validate_webhook_url(url).is_ok()) else {
+        return;
+    };
+    if options.offline {
+        return;
+    }
+    
+    let payload = match result {
+        Ok(record) => WebhookPayload {
+            video_id: Some(record.id.clone()),
+            title: record.title.clone(),
+            status: "success",
+            summary: record.summary_content.clone(),
+            error: None,
+        },
+        Err(e) => WebhookPayload {
+            video_id: trace.video_id.clone(),
+            title: trace.video_id.as_ref()
+                .and_then(|id| load_vault(vault_path).ok()?.videos.get(id)?.title.clone()),
+            status: if matches!(e, PipelineError::Cancelled) { "cancelled" } else { "failed" },
+            summary: None,
+            error: Some(e.to_string()),
+        },
+    };
+    
+    let client = match build_http_client(DownloadOptions::from_pipeline_options(&options).proxy.as_deref()) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!("发送 webhook 通知失败: {}", e);
+            return;
+        }
+    };
+    let response = client.post(webhook_url)
+        .timeout(std::time::Duration::from_secs(WEBHOOK_TIMEOUT_SECONDS))
+        .json(&payload)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = response {
+        tracing::warn!("发送 webhook 通知失败: {}", e);
+    }
+}
+
+async fn run_pipeline_steps(app: &AppHandle, registry: &PipelineRegistry, url: &str, vault_path: &PathBuf, options: &PipelineOptions, trace: &mut PipelineTrace) -> Result<VideoRecord, PipelineError> {
+    let PipelineTrace { stages, warnings, video_id: traced_id } = trace;
+    let config = load_vault_config(vault_path)?;
+    let options = &options.clone().with_defaults(&config);
+    if let Some(webhook_url) = &options.webhook_url {
+        validate_webhook_url(webhook_url)?;
+    }
+    
+    // 在开始下载前校验转录参数，避免下载完才发现参数错误
+    let transcribe_options = TranscribeOptions::from_pipeline_options(options);
+    transcribe_options.validate()?;
+    // 原语言转录总是 transcribe，需要译文时之后再单独运行一次 translate
+    let translate = transcribe_options.task == WHISPER_TASK_TRANSLATE;
+    let transcribe_options = TranscribeOptions { task: WHISPER_TASK_TRANSCRIBE.to_string(), ..transcribe_options };
+    let download_options = DownloadOptions::from_pipeline_options(options);
+    download_options.validate()?;
+    let summary_options = SummaryOptions::from_pipeline_options(options, download_options.proxy.clone())?;
+    summary_options.validate()?;
+    
+    let time_range = download_options.time_range()?;
+    if time_range.is_some() && local_file_path(url).is_some() {
+        return Err(PipelineError::InvalidInput("本地文件不支持截取片段".to_string()));
+    }
+    
+    // 加载vault
+    let vault = load_vault(vault_path)?;
+    
+    let video_id = resolve_video_id(&vault, url, time_range);
+    *traced_id = Some(video_id.clone());
+    let progress = ProgressEmitter { app: app.clone(), video_id: video_id.clone() };
+    let running = registry.start(&video_id)?;
+    let cancel = &running.token;
+    
+    let timestamp = get_current_timestamp();
+    
+    // 检查是否已有记录
+    let new_record = !vault.videos.contains_key(&video_id);
+    let mut record = vault.videos.get(&video_id).cloned().unwrap_or_else(|| VideoRecord {
+        id: video_id.clone(),
+        url: url.to_string(),
+        time_range,
+        created_at: timestamp.clone(),
+        updated_at: timestamp.clone(),
+        ..Default::default()
+    });
+    
+    // 新视频先下载到平铺目录，拿到发布日期和上传者后再按布局移动
+    let video_dir = get_video_dir_path(vault_path, &record);
+    fs::create_dir_all(&video_dir)
+        .map_err(|e| PipelineError::VaultIo(format!("创建视频目录失败: {}", e)))?;
+    
+    // 如果记录显示已下载但缺少 audio_file 路径，尝试找到文件
+    if record.downloaded && !record.audio_purged && record.audio_file.is_none() {
+        if let Some(found) = find_audio_file(&video_dir, record.audio_format.as_deref()) {
+            if let Some(warning) = found.ambiguity_warning() {
+                progress.emit("download", None, &warning);
+                warnings.push(warning);
+            }
+            record.audio_file = Some(found.path);
+            record.updated_at = get_current_timestamp();
+            save_record(vault_path, &record).await?;
+        }
+    }
+    
+    // 本地导入的文件不经过 yt-dlp
+    let local_source = local_file_path(url);
+    
+    // 旧记录可能缺少时长、文件大小和音频哈希，补齐后保存
+    let missing_duration = record.duration_seconds.is_none() && local_source.is_none();
+    if record.downloaded && !record.audio_purged && (missing_duration || record.audio_size_bytes.is_none() || record.audio_sha256.is_none()) {
+        if missing_duration {
+            record.duration_seconds = match time_range {
+                Some(range) => Some(range.end - range.start),
+                // 时长只是补充信息，查询超时不影响后续步骤
+                None => fetch_video_duration(url, &download_options).await.unwrap_or_else(|e| {
+                    tracing::warn!("补充视频时长失败: {}", e);
+                    None
+                }),
+            };
+        }
+        if record.audio_size_bytes.is_none() {
+            record.audio_size_bytes = record.audio_file.as_deref().and_then(get_file_size);
+        }
+        if record.audio_sha256.is_none() {
+            record.audio_sha256 = record.audio_file.as_deref().and_then(hash_file_sha256);
+        }
+        record.updated_at = get_current_timestamp();
+        save_record(vault_path, &record).await?;
+    }
+    
+    if options.force_redownload && record.downloaded {
+        // yt-dlp 不会覆盖已存在的文件，必须先删掉旧的音频和视频
+        let stale_files = record.audio_file.take().into_iter().chain(record.video_file.take());
+        for path in stale_files {
+            if let Err(e) = fs::remove_file(&path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    return Err(PipelineError::VaultIo(format!("删除旧音频文件失败 {}: {}", path, e)));
+                }
+            }
+        }
+        // 分段转录的中间文件来自旧音频
+        let chunks_dir = video_dir.join(TRANSCRIBE_CHUNKS_DIR);
+        if chunks_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&chunks_dir) {
+                tracing::warn!("删除旧的分段文件失败: {}", e);
+            }
+        }
+        record.downloaded = false;
+        record.audio_purged = false;
+        record.audio_size_bytes = None;
+        record.audio_sha256 = None;
+        record.updated_at = get_current_timestamp();
+        save_record(vault_path, &record).await?;
+        progress.emit("download", None, "已删除旧的音频文件，准备重新下载");
+    }
+    
+    if options.force_retranscribe && record.transcribed {
+        clear_transcription(&mut record);
+        record.updated_at = get_current_timestamp();
+        save_record(vault_path, &record).await?;
+        progress.emit("transcribe", None, "已清除旧的转录结果，准备重新转录");
+    }
+    
+    // 音频已在上次转录后删除，需要转录时重新下载
+    if record.audio_purged && !record.transcribed {
+        record.downloaded = false;
+        record.audio_purged = false;
+        progress.emit("download", None, "音频文件已删除，重新下载后转录");
+    }
+    
+    // Step 1: 下载视频
+    if !record.downloaded {
+        check_cancelled(cancel)?;
+        stages.push(PipelineStage::new(StageName::Download, StageStatus::Running, None));
+        progress.emit("download", Some(0.0), "正在下载视频...");
+        if let Some(warning) = download_options.format_selector_warning().filter(|_| local_source.is_none()) {
+            progress.emit("download", None, &warning);
+            warnings.push(warning);
+        }
+        let on_progress = |percent: Option<f32>, message: &str| progress.emit("download", percent, message);
+        let downloaded = match &local_source {
+            Some(source) => import_local_file(source, &video_dir).await,
+            None => download_video_to_dir(url, &video_dir, &download_options, on_progress, cancel).await,
+        };
+        match downloaded {
+            Ok(downloaded) => {
+                record.downloaded = true;
+                record.audio_file = Some(downloaded.audio_file);
+                record.audio_format = Some(downloaded.audio_format);
+                record.title = Some(downloaded.title);
+                record.duration_seconds = downloaded.duration_seconds;
+                record.thumbnail_file = downloaded.thumbnail_file;
+                record.video_file = downloaded.video_file;
+                record.metadata_file = downloaded.metadata_file;
+                record.upload_date = downloaded.upload_date;
+                record.uploader = downloaded.uploader;
+                record.audio_size_bytes = record.audio_file.as_deref().and_then(get_file_size);
+                record.audio_sha256 = record.audio_file.as_deref().and_then(hash_file_sha256);
+                record.authenticated_download = local_source.is_none() && download_options.uses_cookies();
+                record.format_selector = download_options.format_selector.clone().filter(|_| local_source.is_none());
+                record.custom_headers = local_source.is_none() && download_options.uses_custom_headers();
+                record.updated_at = get_current_timestamp();
+                
+                // 移动失败不影响后续步骤，文件留在原目录
+                let layout_dir = layout_video_dir(config.dir_layout.unwrap_or_default(), &record);
+                if let Err(e) = relocate_video_dir(vault_path, &mut record, layout_dir) {
+                    let warning = format!("按目录布局移动失败，保留在原目录: {}", e);
+                    progress.emit("download", None, &warning);
+                    warnings.push(warning);
+                }
+                if local_source.is_none() && record.thumbnail_file.is_none() {
+                    warnings.push("未能获取视频缩略图".to_string());
+                }
+                
+                // 保存进度
+                save_record(vault_path, &record).await?;
+                
+                progress.emit("download", Some(100.0), "下载完成");
+                finish_stage(stages, record.audio_file.clone());
+            }
+            Err(_) if cancel.is_cancelled() => {
+                // 新记录的目录中只有未完成的下载，直接清空；已有记录的目录中还有转录、字幕等文件，只删除下载残留
+                if new_record {
+                    if let Err(e) = fs::remove_dir_all(&video_dir) {
+                        tracing::warn!("清理未完成的下载失败: {}", e);
+                    }
+                } else {
+                    remove_download_leftovers(&video_dir);
+                }
+                progress.emit("download", None, PIPELINE_CANCELLED);
+                return Err(PipelineError::Cancelled);
+            }
+            Err(e @ PipelineError::Timeout(_)) => {
+                // 保留 .part 文件，下次运行时 yt-dlp 会从中断处继续下载
+                progress.emit("download", None, "下载超时，已下载的部分会在下次运行时继续");
+                return Err(e);
+            }
+            Err(e) => return Err(e)
+        }
+    } else {
+        progress.emit("download", Some(100.0), "视频已下载，跳过下载步骤");
+        stages.push(PipelineStage::new(StageName::Download, StageStatus::Skipped, record.audio_file.clone()));
+    }
+    
+    // Step 2: 转录音频
+    if !record.transcribed {
+        if let Some(audio_file) = record.audio_file.clone() {
+            let audio_file = &audio_file;
+            // 转录结果总是写入视频目录，与音频实际所在的位置无关
+            let transcribe_options = TranscribeOptions {
+                output_dir: Some(get_video_dir_path(vault_path, &record)),
+                ..transcribe_options.clone()
+            };
+            check_cancelled(cancel)?;
+            stages.push(PipelineStage::new(StageName::Transcribe, StageStatus::Running, None));
+            progress.emit("transcribe", Some(0.0), "正在转录音频...");
+            // 明确要求重新转录时不复用其他记录的结果
+            let duplicate = if options.force_retranscribe {
+                None
+            } else {
+                find_duplicate_transcription(&load_vault(vault_path)?, &record, &transcribe_options)
+            };
+            // 复用已有结果不需要运行 whisper，不必排队
+            let queue = app.state::<TranscriptionQueue>();
+            let slot = match duplicate {
+                Some(_) => None,
+                None => Some(queue.acquire(&progress, cancel).await?),
+            };
+            let transcription = match (duplicate, transcribe_options.chunk_seconds) {
+                (Some(source), _) => {
+                    progress.emit("transcribe", None, &format!("音频与已转录的视频 {} 相同，复用其转录结果", source.id));
+                    copy_transcription(&source, audio_file, &transcribe_options)
+                }
+                (None, Some(chunk_seconds)) => {
+                    // 分段设置改变后之前的中间结果无法复用
+                    let resume = record.chunked_transcription.clone()
+                        .filter(|state| state.chunk_seconds == chunk_seconds);
+                    let on_chunk_done = |state: &ChunkedTranscription| {
+                        record.chunked_transcription = Some(state.clone());
+                        record.updated_at = get_current_timestamp();
+                        let record = record.clone();
+                        async move { save_record(vault_path, &record).await }
+                    };
+                    transcribe_audio_in_chunks(audio_file, &transcribe_options, chunk_seconds, resume, on_chunk_done, &progress, cancel).await
+                }
+                (None, None) => transcribe_audio_file(audio_file, &transcribe_options, &progress, |segment| progress.emit_transcript_chunk(segment), cancel).await,
+            };
+            drop(slot);
+            match transcription {
+                Ok(output) => {
+                    record.chunked_transcription = None;
+                    record.transcribed = true;
+                    record.transcript_content = Some(output.content);
+                    record.transcript_file = Some(output.transcript_file);
+                    record.subtitle_files = output.subtitle_files;
+                    record.segments = output.segments;
+                    record.whisper_model = Some(transcribe_options.model.clone());
+                    record.transcribe_language = transcribe_options.language.clone().or(output.detected_language);
+                    warnings.extend(output.warnings);
+                    record.updated_at = get_current_timestamp();
+                    
+                    // 保存进度
+                    save_record(vault_path, &record).await?;
+                    
+                    // 译文只是附加结果，失败时记录警告，不影响原语言转录
+                    if translate && record.translation_content.is_none() {
+                        let translate_options = TranscribeOptions {
+                            task: WHISPER_TASK_TRANSLATE.to_string(),
+                            subtitle_formats: Vec::new(),
+                            include_segments: false,
+                            chunk_seconds: None,
+                            diarize: false,
+                            output_dir: Some(get_video_dir_path(vault_path, &record).join(TRANSLATION_DIR)),
+                            ..transcribe_options.clone()
+                        };
+                        progress.emit("transcribe", None, "正在翻译为英文...");
+                        let translation = match translate_options.output_dir.as_deref().map(fs::create_dir_all) {
+                            Some(Err(e)) => Err(PipelineError::VaultIo(format!("创建译文目录失败: {}", e))),
+                            _ => {
+                                let _slot = queue.acquire(&progress, cancel).await?;
+                                // 译文不推送片段，transcript-chunk 只对应原语言转录
+                                transcribe_audio_file(audio_file, &translate_options, &progress, |_| {}, cancel).await
+                            }
+                        };
+                        match translation {
+                            Ok(output) => {
+                                record.translation_content = Some(output.content);
+                                record.translation_file = Some(output.transcript_file);
+                                record.updated_at = get_current_timestamp();
+                                save_record(vault_path, &record).await?;
+                            }
+                            Err(_) if cancel.is_cancelled() => return Err(PipelineError::Cancelled),
+                            Err(e) => {
+                                let warning = format!("翻译为英文失败: {}", e);
+                                progress.emit("transcribe", None, &warning);
+                                warnings.push(warning);
+                            }
+                        }
+                    }
+                    
+                    // 转录结果保存后再删除音频，删除失败时保留记录中的路径
+                    if !options.keep_audio.unwrap_or(true) {
+                        match purge_audio_file(&mut record) {
+                            Ok(()) => {
+                                save_record(vault_path, &record).await?;
+                                progress.emit("transcribe", None, "已删除音频文件");
+                            }
+                            Err(e) => {
+                                let warning = format!("删除音频文件失败: {}", e);
+                                progress.emit("transcribe", None, &warning);
+                                warnings.push(warning);
+                            }
+                        }
+                    }
+                    
+                    progress.emit("transcribe", Some(100.0), "转录完成");
+                    finish_stage(stages, record.transcript_file.clone());
+                }
+                Err(_) if cancel.is_cancelled() => {
+                    if let Some(transcript_file) = find_transcript_file(audio_file, &transcribe_options.output_dir(audio_file), "txt") {
+                        if let Err(e) = fs::remove_file(&transcript_file) {
+                            tracing::warn!("清理未完成的转录文件失败: {}", e);
+                        }
+                    }
+                    progress.emit("transcribe", None, PIPELINE_CANCELLED);
+                    return Err(PipelineError::Cancelled);
+                }
+                Err(e) => return Err(e)
+            }
+        } else {
+            return Err(PipelineError::TranscriptionFailed("未找到音频文件路径".to_string()));
+        }
+    } else if record.transcribed {
+        progress.emit("transcribe", Some(100.0), "音频已转录，跳过转录步骤");
+        stages.push(PipelineStage::new(StageName::Transcribe, StageStatus::Skipped, record.transcript_file.clone()));
+    }
+    
+    // Step 3: 生成总结
+    if !record.summarized && record.transcript_content.is_some() {
+        check_cancelled(cancel)?;
+        stages.push(PipelineStage::new(StageName::Summarize, StageStatus::Running, None));
+        progress.emit("summarize", Some(0.0), "正在生成总结...");
+        let transcript = record.transcript_content.as_ref().unwrap();
+        match summarize_transcript_content(transcript, &summary_options).await {
+            Ok(summary) => {
+                record.summarized = true;
+                record.summary_content = Some(summary.text);
+                record.word_count = Some(summary.word_count);
+                record.reading_minutes = Some(summary.reading_minutes);
+                record.summary_provider = Some(summary.provider);
+                record.summary_model = summary.model;
+                record.summary_style = summary.style;
+                record.keywords = Some(summary.keywords);
+                record.summaries = summary.summaries;
+                record.updated_at = get_current_timestamp();
+                warnings.extend(summary.warnings);
+                // 章节总结只是附加结果，接口不可用而回退到简单总结时不再请求
+                if record.summary_provider.as_deref() != Some(SIMPLE_SUMMARY_PROVIDER) {
+                    match summarize_chapters(&record, &summary_options).await {
+                        Ok(chapter_summaries) => record.chapter_summaries = chapter_summaries,
+                        Err(e) => warnings.push(format!("生成章节总结失败: {}", e)),
+                    }
+                }
+                if record.summary_provider.as_deref() == Some(SIMPLE_SUMMARY_PROVIDER) && !summary_options.offline {
+                    warnings.push(if summary_options.api_key.is_none() && summary_options.provider.requires_api_key() {
+                        "未配置 API 密钥，使用了简单总结".to_string()
+                    } else {
+                        "总结接口暂时不可用，使用了简单总结".to_string()
+                    });
+                }
+                
+                // 保存最终进度
+                save_record(vault_path, &record).await?;
+                
+                progress.emit("summarize", Some(100.0), "总结完成");
+                finish_stage(stages, record.summary_provider.clone());
+            }
+            Err(e) => {
+                // 下载和转录结果已经保存，总结失败时仍返回记录，错误记在总结步骤中
+                progress.emit("summarize", None, &format!("总结失败: {}", e));
+                if let Some(stage) = stages.last_mut() {
+                    stage.status = StageStatus::Failed;
+                    stage.detail = Some(e.to_string());
+                }
+            }
+        }
+    } else if record.summarized {
+        progress.emit("summarize", Some(100.0), "内容已总结，跳过总结步骤");
+        stages.push(PipelineStage::new(StageName::Summarize, StageStatus::Skipped, record.summary_provider.clone()));
+    }
+    
+    Ok(record)
+}
+
+#[tauri::command]
+fn queue_status(queue: State<'_, TranscriptionQueue>) -> QueueStatus {
+    queue.status()
+}
+
+#[tauri::command]
+fn set_transcription_concurrency(queue: State<'_, TranscriptionQueue>, limit: usize) -> Result<QueueStatus, PipelineError> {
+    if !(1..=MAX_TRANSCRIBE_CONCURRENCY).contains(&limit) {
+        return Err(PipelineError::InvalidInput(format!("转录并发数必须在 1 到 {} 之间", MAX_TRANSCRIBE_CONCURRENCY)));
+    }
+    queue.set_limit(limit);
+    Ok(queue.status())
+}
+
+fn check_cancelled(cancel: &CancelToken) -> Result<(), PipelineError> {
+    if cancel.is_cancelled() {
+        Err(PipelineError::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+#[tauri::command]
+fn cancel_pipeline(video_id: String, registry: State<'_, PipelineRegistry>) -> Result<(), PipelineError> {
+    match registry.running.lock().unwrap().get(&video_id) {
+        Some(token) => {
+            token.cancel();
+            Ok(())
+        }
+        None => Err(PipelineError::NotFound("没有正在运行的流水线".to_string()))
+    }
+}
+
+/// 下载完成后得到的音频文件及视频信息
+struct DownloadedAudio {
+    audio_file: String,
+    audio_format: String,
+    title: String,
+    duration_seconds: Option<u64>,
+    thumbnail_file: Option<String>,
+    metadata_file: Option<String>,
+    upload_date: Option<String>,
+    uploader: Option<String>,
+    video_file: Option<String>,
+}
+
+/// save_metadata 开启时保存 yt-dlp 完整元数据的文件名
+const METADATA_FILE_NAME: &str = "metadata.json";
+
+/// 可以直接导入转录的本地文件扩展名，视频文件由 whisper 通过 ffmpeg 读取音轨
+const LOCAL_MEDIA_EXTENSIONS: [&str; 12] = ["wav", "mp3", "m4a", "aac", "flac", "ogg", "opus", "mp4", "mkv", "mov", "webm", "avi"];
+
+/// 检查本地文件存在且扩展名受支持，返回绝对路径
+fn validate_local_media_file(file_path: &str) -> Result<PathBuf, PipelineError> {
+    let path = PathBuf::from(expand_tilde_path(file_path.trim()));
+    if !path.is_file() {
+        return Err(PipelineError::InvalidInput(format!("文件不存在: {}", path.display())));
+    }
+    
+    let extension = path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !LOCAL_MEDIA_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(PipelineError::InvalidInput(format!(
+            "不支持的文件类型: {}，可选扩展名: {}",
+            path.display(),
+            LOCAL_MEDIA_EXTENSIONS.join(", ")
+        )));
+    }
+    
+    path.canonicalize()
+        .map_err(|e| PipelineError::InvalidInput(format!("无法解析文件路径 {}: {}", path.display(), e)))
+}
+
+/// 记录的 url 是 file:// URI 时返回对应的本地路径
+fn local_file_path(url: &str) -> Option<PathBuf> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    if parsed.scheme() != "file" {
+        return None;
+    }
+    parsed.to_file_path().ok()
+}
+
+/// 把本地文件复制到视频目录，代替下载步骤
+async fn import_local_file(source: &Path, output_dir: &Path) -> Result<DownloadedAudio, PipelineError> {
+    let file_name = source.file_name()
+        .ok_or_else(|| PipelineError::InvalidInput(format!("无效的文件路径: {}", source.display())))?;
+    if !source.is_file() {
+        return Err(PipelineError::InvalidInput(format!("本地文件已不存在: {}", source.display())));
+    }
+    
+    let target = output_dir.join(file_name);
+    tokio::fs::copy(source, &target).await
+        .map_err(|e| PipelineError::VaultIo(format!("复制本地文件失败: {}", e)))?;
+    
+    Ok(DownloadedAudio {
+        audio_file: target.to_string_lossy().to_string(),
+        audio_format: source.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default(),
+        title: source.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        duration_seconds: None,
+        thumbnail_file: None,
+        metadata_file: None,
+        upload_date: None,
+        uploader: None,
+        video_file: None,
+    })
+}
+
+/// 下载音频到指定目录。`on_progress` 在每次解析到 yt-dlp 的下载百分比时调用，
+/// 重试等状态提示也通过它上报（此时百分比为 None），下载器本身不依赖前端事件。
+#[tracing::instrument(level = "debug", name = "download", skip_all, fields(url))]
+async fn download_video_to_dir(url: &str, output_dir: &PathBuf, options: &DownloadOptions, on_progress: impl Fn(Option<f32>, &str), cancel: &CancelToken) -> Result<DownloadedAudio, PipelineError> {
+    // 先检查yt-dlp是否可用
+    match probe_tool_version(&options.yt_dlp, "--version").await {
+        Err(e @ PipelineError::Timeout(_)) => return Err(e),
+        Err(_) => return Err(PipelineError::ToolNotFound("yt-dlp未安装或不在PATH中。请先安装yt-dlp: pip install yt-dlp".to_string())),
+        Ok(None) => return Err(PipelineError::ToolNotFound("yt-dlp无法正常运行，请检查安装".to_string())),
+        Ok(Some(_)) => {}
+    }
+    
+    // yt-dlp 截取片段依赖 ffmpeg
+    let time_range = options.time_range()?;
+    if time_range.is_some() {
+        match probe_tool_version(FFMPEG_BINARY, "-version").await {
+            Ok(Some(_)) => {}
+            Err(e @ PipelineError::Timeout(_)) => return Err(e),
+            _ => return Err(PipelineError::ToolNotFound("截取视频片段需要 ffmpeg，请先安装 ffmpeg 并确保其在 PATH 中".to_string())),
+        }
+    }
+    
+    // 先获取视频信息（时长、标题和可用性检查），开启 save_metadata 时把完整的 JSON 元数据保存到视频目录
+    let info = fetch_video_info(url, options).await?;
+    let mut metadata_file = None;
+    if let Some(metadata) = &info.metadata_json {
+        let metadata_path = output_dir.join(METADATA_FILE_NAME);
+        fs::write(&metadata_path, metadata)
+            .map_err(|e| PipelineError::VaultIo(format!("保存视频元数据失败: {}", e)))?;
+        metadata_file = Some(metadata_path.to_string_lossy().to_string());
+    }
+    
+    // 下载并转换为音频，遇到临时性网络错误时按 1s、2s、4s... 退避重试
+    let mut attempts = 0;
+    let output = loop {
+        attempts += 1;
+        let command = build_download_command(url, output_dir, options);
+        let output = run_streaming(command, cancel, Some(options.timeout), |line| {
+            if let Some(percent) = parse_download_percent(line) {
+                on_progress(Some(percent), line.trim());
+            }
+        }).await;
+        
+        let should_retry = matches!(&output, Ok(result) if !result.status.success() && is_transient_download_error(&result.stderr));
+        if !should_retry || attempts >= options.max_attempts {
+            break output;
+        }
+        
+        let delay = tokio::time::Duration::from_secs(1 << (attempts - 1).min(6));
+        on_progress(None, &format!("网络错误，{} 秒后重试 ({}/{})", delay.as_secs(), attempts, options.max_attempts));
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancel.cancelled() => return Err(PipelineError::Cancelled),
+        }
+    };
+
+    match output {
+        Ok(result) => {
+            let stdout = &result.stdout;
+            let stderr = &result.stderr;
+            
+            if result.status.success() {
+                // 等待一小段时间确保文件写入完成
+                tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+                
+                // --keep-video 也会保留合并前的单独画面和音轨（`<标题>.f137.mp4` 这样的文件），只留下合并后的视频
+                let video_file = if options.keep_video {
+                    remove_format_fragments(output_dir);
+                    find_video_file(output_dir)
+                } else {
+                    None
+                };
+                
+                if let Some(found) = find_audio_file(output_dir, Some(&options.audio_format)) {
+                    if let Some(warning) = found.ambiguity_warning() {
+                        on_progress(None, &warning);
+                    }
+                    // 缩略图只用于展示，缺失时不影响流水线
+                    let thumbnail_file = find_thumbnail_file(output_dir);
+                    if thumbnail_file.is_none() {
+                        tracing::warn!("未找到视频缩略图: {}", output_dir.display());
+                    }
+                    Ok(DownloadedAudio {
+                        audio_file: found.path,
+                        audio_format: options.audio_format.clone(),
+                        title: info.title,
+                        // 截取片段时记录片段本身的时长
+                        duration_seconds: time_range.map(|range| range.end - range.start).or(info.duration_seconds),
+                        thumbnail_file,
+                        metadata_file,
+                        upload_date: info.upload_date,
+                        uploader: info.uploader,
+                        video_file,
+                    })
+                } else {
+                    // 如果找不到文件，提供详细的调试信息
+                    let dir_contents = list_directory_contents(output_dir);
+                    Err(PipelineError::DownloadFailed(format!(
+                        "下载似乎成功但未找到音频文件。\n目录: {}\n目录内容: {:?}\n\nyt-dlp输出:\nSTDOUT: {}\nSTDERR: {}", 
+                        output_dir.display(), 
+                        dir_contents,
+                        stdout.trim(),
+                        stderr.trim()
+                    )))
+                }
+            } else {
+                Err(download_failure(format!("yt-dlp下载失败 (退出码: {}，共尝试 {} 次)\nSTDOUT: {}\nSTDERR: {}", 
+                    result.status.code().unwrap_or(-1),
+                    attempts,
+                    stdout.trim(),
+                    stderr.trim()
+                ), stderr))
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            Err(PipelineError::Timeout(format!("yt-dlp 下载超过 {} 分钟未完成", options.timeout.as_secs() / 60)))
+        }
+        Err(e) => Err(yt_dlp_spawn_error(&e))
+    }
+}
+
+/// 下载前通过 yt-dlp 查询到的视频信息
+struct VideoInfo {
+    title: String,
+    duration_seconds: Option<u64>,
+    upload_date: Option<String>,
+    uploader: Option<String>,
+    /// 开启 save_metadata 时 yt-dlp 输出的完整 JSON 元数据
+    metadata_json: Option<Vec<u8>>,
+}
+
+/// 只查询视频信息不下载。默认只打印需要的字段，每个字段一行，标题以 JSON 字符串输出，其中的换行会被转义；
+/// 开启 save_metadata 时改为导出完整的 JSON 元数据，原始内容保存在 metadata_json 中。
+/// 链接包含多个视频时报错，应改用 process_playlist 处理
+async fn fetch_video_info(url: &str, options: &DownloadOptions) -> Result<VideoInfo, PipelineError> {
+    let mut info_command = options.yt_dlp_command();
+    if options.save_metadata {
+        info_command.arg("--dump-single-json");
+    } else {
+        info_command
+            .arg("--print").arg("%(duration)s")
+            .arg("--print").arg("%(upload_date)s")
+            .arg("--print").arg("%(uploader)s")
+            .arg("--print").arg("%(title)j");
+    }
+    info_command.arg("--no-download");
+    options.apply_network_args(&mut info_command);
+    info_command.arg(url);
+    tracing::debug!("运行 {}", describe_command(&info_command));
+    // 超时后 output() 的 future 被丢弃，kill_on_drop 保证子进程随之终止
+    let info_output = tokio::time::timeout(options.timeout, info_command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| PipelineError::Timeout(format!("获取视频信息超过 {} 分钟未完成", options.timeout.as_secs() / 60)))?;
+        
+    match info_output {
+        Ok(result) if result.status.success() && options.save_metadata => {
+            let metadata = serde_json::from_slice::<serde_json::Value>(&result.stdout)
+                .map_err(|e| PipelineError::DownloadFailed(format!("解析视频元数据失败: {}", e)))?;
+            if metadata["_type"].as_str() == Some("playlist") {
+                return Err(multiple_videos_error());
+            }
+            let text_field = |key: &str| metadata[key].as_str().map(|value| value.trim().to_string());
+            Ok(VideoInfo {
+                duration_seconds: metadata["duration"].as_f64().map(|seconds| seconds.round() as u64),
+                title: text_field("title").map(|title| normalize_title(&title)).unwrap_or_default(),
+                upload_date: text_field("upload_date"),
+                uploader: text_field("uploader"),
+                metadata_json: Some(result.stdout),
+            })
+        }
+        Ok(result) if result.status.success() => {
+            let stdout = decode_tool_output(&result.stdout);
+            let lines: Vec<&str> = stdout.trim().lines().collect();
+            // 播放列表会为每个条目各打印一组字段，出现多个不同的标题说明链接包含多个视频
+            let titles: HashSet<String> = lines.iter().skip(3).step_by(4)
+                .filter_map(|line| parse_printed_title(line))
+                .collect();
+            if lines.len() > 4 && titles.len() > 1 {
+                return Err(multiple_videos_error());
+            }
+            // yt-dlp 对缺失的字段打印 NA
+            let field = |index: usize| lines.get(index)
+                .map(|line| line.trim().to_string())
+                .filter(|value| !value.is_empty() && value != "NA");
+            Ok(VideoInfo {
+                duration_seconds: field(0).and_then(|duration| parse_duration_seconds(&duration)),
+                upload_date: field(1),
+                uploader: field(2),
+                title: lines.get(3).and_then(|line| parse_printed_title(line)).unwrap_or_default(),
+                metadata_json: None,
+            })
+        }
+        Ok(result) => {
+            let stderr = decode_tool_output(&result.stderr);
+            Err(download_failure(format!("无法获取视频信息: {}", stderr), &stderr))
+        }
+        Err(e) => Err(yt_dlp_spawn_error(&e))
+    }
+}
+
+/// 解析 `%(title)j` 打印的 JSON 字符串，旧版 yt-dlp 不支持 j 转换时按原文处理；缺少标题时为 null 或 NA
+fn parse_printed_title(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line == "NA" || line == "null" {
+        return None;
+    }
+    let title = serde_json::from_str::<String>(line).unwrap_or_else(|_| line.to_string());
+    Some(normalize_title(&title)).filter(|title| !title.is_empty())
+}
+
+/// 把标题中的换行和连续空白合并为单个空格
+fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn multiple_videos_error() -> PipelineError {
+    PipelineError::InvalidInput("该链接包含多个视频，请使用播放列表处理 (process_playlist)".to_string())
+}
+
+fn yt_dlp_spawn_error(e: &std::io::Error) -> PipelineError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        PipelineError::ToolNotFound("yt-dlp未安装或不在PATH中。请先安装yt-dlp: pip install yt-dlp".to_string())
+    } else {
+        PipelineError::DownloadFailed(format!("执行 yt-dlp 失败: {}", e))
+    }
+}
+
+/// 为缺少时长的旧记录单独查询一次，超时返回 Timeout，其他查询失败时返回 Ok(None)
+async fn fetch_video_duration(url: &str, options: &DownloadOptions) -> Result<Option<u64>, PipelineError> {
+    let mut command = options.yt_dlp_command();
+    command
+        .arg("--print").arg("%(duration)s")
+        .arg("--no-download");
+    options.apply_network_args(&mut command);
+    command.arg(url);
+    let output = tokio::time::timeout(options.timeout, command.kill_on_drop(true).output())
+        .await
+        .map_err(|_| PipelineError::Timeout(format!("获取视频时长超过 {} 分钟未完成", options.timeout.as_secs() / 60)))?;
+    
+    match output {
+        Ok(output) if output.status.success() => Ok(parse_duration_seconds(&decode_tool_output(&output.stdout))),
+        _ => Ok(None),
+    }
+}
+
+/// yt-dlp 输出的时长可能是整数、小数或 `NA`
+fn parse_duration_seconds(value: &str) -> Option<u64> {
+    value.trim().parse::<f64>().ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(|seconds| seconds.round() as u64)
+}
+
+fn get_file_size(path: &str) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+fn build_download_command(url: &str, output_dir: &Path, options: &DownloadOptions) -> tokio::process::Command {
+    let mut command = options.yt_dlp_command();
+    command
+        .arg("--extract-audio")
+        .arg("--audio-format").arg(&options.audio_format)
+        .arg("--audio-quality").arg(&options.audio_quality)
+        .arg("--output").arg(output_dir.join("%(title)s.%(ext)s"))
+        // 上次中断留下的 .part 文件继续下载，已完成的文件不重新下载
+        .arg("--continue")
+        .arg("--no-overwrites")
+        .arg("--newline")  // 每次进度更新单独输出一行，便于逐行解析
+        .arg("--verbose")  // 详细输出用于调试
+        .arg("--write-thumbnail")
+        .arg("--convert-thumbnails").arg("jpg");
+    if options.keep_video {
+        // 下载最佳画质并合并为 mp4，提取音频后保留视频文件
+        command
+            .arg("--format").arg(options.format_selector.as_deref().unwrap_or("bestvideo*+bestaudio/best"))
+            .arg("--merge-output-format").arg("mp4")
+            .arg("--keep-video");
+    } else if let Some(selector) = &options.format_selector {
+        command.arg("--format").arg(selector);
+    }
+    if let Ok(Some(range)) = options.time_range() {
+        command.arg("--download-sections").arg(range.download_section());
+    }
+    if let Some(rate) = &options.rate_limit {
+        command.arg("--limit-rate").arg(rate);
+    }
+    if let Some(seconds) = options.sleep_interval {
+        command.arg("--sleep-interval").arg(seconds.to_string());
+    }
+    if let Some(seconds) = options.max_sleep_interval {
+        command.arg("--max-sleep-interval").arg(seconds.to_string());
+    }
+    if let Some(age) = options.age_limit {
+        command.arg("--age-limit").arg(age.to_string());
+    }
+    options.apply_network_args(&mut command);
+    command.arg(url);
+    command
+}
+
+/// 按 stderr 区分年龄限制、地区限制和其他下载失败，前端据此提示使用 cookies、代理或 geo_bypass
+fn download_failure(message: String, stderr: &str) -> PipelineError {
+    let stderr = stderr.to_lowercase();
+    let matches = |patterns: &[&str]| patterns.iter().any(|pattern| stderr.contains(&pattern.to_lowercase()));
+    if matches(&AGE_RESTRICTED_ERRORS) {
+        PipelineError::AgeRestricted(message)
+    } else if matches(&GEO_BLOCKED_ERRORS) {
+        PipelineError::GeoBlocked(message)
+    } else {
+        PipelineError::DownloadFailed(message)
+    }
+}
+
+fn is_transient_download_error(stderr: &str) -> bool {
+    if PERMANENT_DOWNLOAD_ERRORS.iter().any(|pattern| stderr.contains(pattern)) {
+        return false;
+    }
+    TRANSIENT_DOWNLOAD_ERRORS.iter().any(|pattern| stderr.contains(pattern))
+}
+
+/// 子进程结束后的退出状态与完整输出
+struct StreamedOutput {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+}
+
+/// 启动子进程并逐行读取 stdout，每读到一行就回调一次；stderr 在后台完整收集。
+/// 收到取消信号时会终止子进程并返回 `Interrupted` 错误，超过 `timeout` 时终止并返回 `TimedOut` 错误。
+async fn run_streaming(mut command: tokio::process::Command, cancel: &CancelToken, timeout: Option<std::time::Duration>, mut on_line: impl FnMut(&str)) -> std::io::Result<StreamedOutput> {
+    let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+    let timed_out = || async move {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
+    
+    tracing::debug!("运行 {}", describe_command(&command));
+    let started = std::time::Instant::now();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    
+    let stdout = child.stdout.take().expect("stdout 已设置为 piped");
+    let mut stderr = child.stderr.take().expect("stderr 已设置为 piped");
+    
+    // stderr 必须并发读取，否则输出过多时管道写满会导致子进程阻塞
+    let stderr_task = tokio::spawn(async move {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf).await;
+        decode_tool_output(&buf)
+    });
+    
+    // 按字节读取每一行再解码，遇到非 UTF-8 的输出时不会中断读取
+    let mut stdout_content = String::new();
+    let mut reader = BufReader::new(stdout);
+    let read_stdout = async {
+        let mut buf = Vec::new();
+        while reader.read_until(b'\n', &mut buf).await? > 0 {
+            let line = decode_tool_output(&buf);
+            let line = line.trim_end_matches(['\r', '\n']);
+            on_line(line);
+            stdout_content.push_str(line);
+            stdout_content.push('\n');
+            buf.clear();
+        }
+        Ok::<(), std::io::Error>(())
+    };
+    
+    tokio::select! {
+        result = read_stdout => result?,
+        _ = cancel.cancelled() => {
+            child.kill().await?;
+            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, PIPELINE_CANCELLED));
+        }
+        _ = timed_out() => {
+            child.kill().await?;
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "子进程运行超时"));
+        }
+    }
+    
+    // 子进程可能关闭 stdout 后仍不退出，等待退出同样受时限约束
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = timed_out() => {
+            child.kill().await?;
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "子进程运行超时"));
+        }
+    };
+    let stderr_content = stderr_task.await.unwrap_or_default();
+    tracing::debug!("{} 退出，状态: {}，耗时 {} ms", command.as_std().get_program().to_string_lossy(), status, started.elapsed().as_millis());
+    
+    Ok(StreamedOutput {
+        status,
+        stdout: stdout_content,
+        stderr: stderr_content,
+    })
+}
+
+/// 这些参数的值可能包含令牌、代理密码或认证请求头，写日志时隐去
+const SECRET_COMMAND_ARGS: [&str; 3] = ["--hf_token", "--proxy", "--add-header"];
+
+/// 日志中记录的命令行，敏感参数的值替换为 `***`
+fn describe_command(command: &tokio::process::Command) -> String {
+    let command = command.as_std();
+    let mut parts = vec![command.get_program().to_string_lossy().to_string()];
+    let mut hide_next = false;
+    for arg in command.get_args() {
+        let arg = arg.to_string_lossy();
+        parts.push(if hide_next { "***".to_string() } else { arg.to_string() });
+        hide_next = SECRET_COMMAND_ARGS.contains(&arg.as_ref());
+    }
+    parts.join(" ")
+}
+
+/// 解析 yt-dlp 的 `[download]  45.2% of ...` 进度行，返回百分比
+fn parse_download_percent(line: &str) -> Option<f32> {
+    let rest = line.trim().strip_prefix("[download]")?;
+    let token = rest.split_whitespace().next()?;
+    token.strip_suffix('%')?.parse::<f32>().ok()
+}
+
+fn list_directory_contents(dir: &PathBuf) -> Vec<String> {
+    if let Ok(entries) = fs::read_dir(dir) {
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect()
+    } else {
+        vec!["无法读取目录".to_string()]
+    }
+}
+
+fn validate_whisper_model(model: &str) -> Result<(), PipelineError> {
+    if WHISPER_MODELS.contains(&model) {
+        Ok(())
+    } else {
+        Err(PipelineError::InvalidInput(format!("不支持的 Whisper 模型: {}，可选值: {}", model, WHISPER_MODELS.join(", "))))
+    }
+}
+
+fn validate_language_code(code: &str) -> Result<(), PipelineError> {
+    if ISO_639_1_CODES.contains(&code) {
+        Ok(())
+    } else {
+        Err(PipelineError::InvalidInput(format!("无法识别的语言代码: {}，请使用 ISO 639-1 两字母代码（如 en、zh、ja）", code)))
+    }
+}
+
+/// `on_segment` 在 whisper 每输出一个片段时调用，用于把部分转录结果实时推送给前端
+#[tracing::instrument(level = "debug", name = "transcribe", skip_all, fields(audio = audio_file_path, model = %options.model, task = %options.task))]
+async fn transcribe_audio_file(audio_file_path: &str, options: &TranscribeOptions, progress: &ProgressEmitter, on_segment: impl Fn(&TranscriptSegment), cancel: &CancelToken) -> Result<TranscriptionOutput, PipelineError> {
+    options.validate()?;
+    
+    // 说话人识别条件不满足时说明原因并继续普通转录
+    let mut warnings = Vec::new();
+    let diarize_token = if options.diarize {
+        match diarization_token(options) {
+            Ok(token) => Some(token),
+            Err(reason) => {
+                let warning = format!("{}，已跳过说话人识别，继续普通转录", reason);
+                progress.emit("transcribe", None, &warning);
+                warnings.push(warning);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    
+    let diarized = diarize_token.is_some();
+    let (backend, binary, command) = match diarize_token {
+        // whisperx 的 json 输出与 Python 版 whisper 格式相同，只是分段多了 speaker 字段
+        Some(token) => {
+            let command = build_whisperx_command(audio_file_path, options, token);
+            (TranscriptionBackend::PythonWhisper, WHISPERX_BINARY.to_string(), command)
+        }
+        None => {
+            let (backend, binary) = match &options.whisper_path {
+                // 指定了程序路径时不再自动检测，未指定后端则按 Python 版 whisper 调用
+                Some(path) => (options.backend.unwrap_or(TranscriptionBackend::PythonWhisper), path.clone()),
+                None => TranscriptionBackend::resolve(options.backend),
+            };
+            let command = match backend {
+                TranscriptionBackend::PythonWhisper => build_python_whisper_command(&binary, audio_file_path, options),
+                TranscriptionBackend::WhisperCpp => build_whisper_cpp_command(&binary, audio_file_path, options)?,
+            };
+            (backend, binary, command)
+        }
+    };
+    
+    // 两种后端都会把每个识别出的片段打印到 stdout，如 `[00:00.000 --> 00:05.000] 文本`
+    let output = run_streaming(command, cancel, Some(options.timeout), |line| {
+        if !line.trim().is_empty() {
+            progress.emit("transcribe", None, line.trim());
+        }
+        if let Some(segment) = parse_segment_line(line) {
+            on_segment(&segment);
+        }
+    }).await;
+
+    match output {
+        Ok(result) => {
+            if result.status.success() {
+                // 查找生成的转录文本文件
+                let output_dir = options.output_dir(audio_file_path);
+                let transcript_file = find_transcript_file(audio_file_path, &output_dir, "txt")
+                    .ok_or_else(|| PipelineError::TranscriptionFailed("未找到转录输出文件".to_string()))?;
+                let mut content = read_text_file(&transcript_file)
+                    .map_err(|e| PipelineError::TranscriptionFailed(format!("读取转录文件失败: {}", e)))?;
+                
+                let mut subtitle_files = HashMap::new();
+                for format in &options.subtitle_formats {
+                    match find_transcript_file(audio_file_path, &output_dir, format) {
+                        Some(subtitle_file) => {
+                            subtitle_files.insert(format.clone(), subtitle_file);
+                        }
+                        None => return Err(PipelineError::TranscriptionFailed(format!("未找到 {} 字幕文件", format)))
+                    }
+                }
+                
+                // 说话人和置信度只记录在 json 中，需要时总是解析 json 重新生成文本
+                let (segments, detected_language) = if options.needs_json() || diarized {
+                    let json_file = find_transcript_file(audio_file_path, &output_dir, "json")
+                        .ok_or_else(|| PipelineError::TranscriptionFailed("未找到 json 转录结果".to_string()))?;
+                    let body = read_text_file(&json_file)
+                        .map_err(|e| PipelineError::TranscriptionFailed(format!("读取 json 转录结果失败: {}", e)))?;
+                    let mut parsed = parse_whisper_json(backend, &body)
+                        .map_err(PipelineError::TranscriptionFailed)?;
+                    if let Some(threshold) = options.min_confidence {
+                        if let Some(warning) = apply_confidence_threshold(&mut parsed.segments, threshold, options.drop_low_confidence) {
+                            progress.emit("transcribe", None, &warning);
+                            warnings.push(warning);
+                        }
+                    }
+                    // 纯文本与分段保持一致，兼容只读取 transcript_content 的旧逻辑
+                    content = parsed.segments.iter()
+                        .map(|segment| {
+                            let text = if segment.low_confidence {
+                                format!("{} {}", LOW_CONFIDENCE_MARKER, segment.text.trim())
+                            } else {
+                                segment.text.trim().to_string()
+                            };
+                            match &segment.speaker {
+                                Some(speaker) => format!("[{}] {}", speaker, text),
+                                None => text,
+                            }
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    (options.include_segments.then_some(parsed.segments), parsed.language)
+                } else {
+                    (None, None)
+                };
+                
+                // 带说话人标签的文本按行区分说话人，不整理段落
+                let content = if options.format_paragraphs && !diarized {
+                    format_transcript(&content)
+                } else {
+                    content.trim().to_string()
+                };
+                Ok(TranscriptionOutput {
+                    content,
+                    transcript_file,
+                    subtitle_files,
+                    segments,
+                    detected_language,
+                    warnings,
+                })
+            } else {
+                Err(PipelineError::TranscriptionFailed(describe_whisper_failure(&result.stderr, options)))
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && diarized => {
+            Err(PipelineError::ToolNotFound(format!("未找到 {}. 请确保已安装 whisperx: pip install whisperx", binary)))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(PipelineError::ToolNotFound(format!("未找到 {}. {}", binary, backend.install_hint())))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            remove_partial_transcription(audio_file_path, options);
+            Err(PipelineError::Timeout(format!("{} 转录超过 {} 分钟未完成", binary, options.timeout.as_secs() / 60)))
+        }
+        Err(e) => Err(PipelineError::TranscriptionFailed(format!("执行 {} 失败: {}. {}", binary, e, backend.install_hint())))
+    }
+}
+
+/// 转录文本中标在低置信度片段前的记号
+const LOW_CONFIDENCE_MARKER: &str = "[?]";
+
+/// 标出 avg_logprob 低于阈值的片段，drop 为 true 时直接去掉，返回给用户的提示。
+/// 没有置信度的片段（如 whisper.cpp 的输出）保持不变。只影响转录文本和分段，whisper 直接写出的字幕文件不变
+fn apply_confidence_threshold(segments: &mut Vec<TranscriptSegment>, threshold: f64, drop: bool) -> Option<String> {
+    let is_low = |segment: &TranscriptSegment| segment.avg_logprob.is_some_and(|logprob| logprob < threshold);
+    if segments.iter().all(|segment| segment.avg_logprob.is_none()) {
+        return Some("转录结果中没有置信度信息（whisper.cpp 不提供 avg_logprob），未按 min_confidence 过滤".to_string());
+    }
+    let total = segments.len();
+    if drop {
+        segments.retain(|segment| !is_low(segment));
+        let dropped = total - segments.len();
+        (dropped > 0).then(|| format!("已去掉 {}/{} 个置信度低于 {} 的片段", dropped, total, threshold))
+    } else {
+        for segment in segments.iter_mut() {
+            segment.low_confidence = is_low(segment);
+        }
+        let flagged = segments.iter().filter(|segment| segment.low_confidence).count();
+        (flagged > 0).then(|| format!("{}/{} 个片段置信度低于 {}，已在转录文本中用 {} 标出", flagged, total, threshold, LOW_CONFIDENCE_MARKER))
+    }
+}
+
+/// 解析 whisper 打印的片段行。Python 版为 `[00:05.000 --> 00:09.500]  文本`（超过一小时时带小时），
+/// whisper.cpp 为 `[00:00:05.000 --> 00:00:09.500]   文本`
+fn parse_segment_line(line: &str) -> Option<TranscriptSegment> {
+    let (times, text) = line.trim().strip_prefix('[')?.split_once(']')?;
+    let (start, end) = times.split_once("-->")?;
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(TranscriptSegment {
+        start: parse_segment_timestamp(start)?,
+        end: parse_segment_timestamp(end)?,
+        text: text.to_string(),
+        speaker: None,
+        avg_logprob: None,
+        no_speech_prob: None,
+        low_confidence: false,
+    })
+}
+
+/// `MM:SS.mmm` 或 `HH:MM:SS.mmm`，返回秒数
+fn parse_segment_timestamp(value: &str) -> Option<f64> {
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let (seconds, minutes) = parts.split_last()?;
+    let seconds: f64 = seconds.parse().ok().filter(|seconds: &f64| seconds.is_finite() && *seconds >= 0.0)?;
+    let minutes = minutes.iter().try_fold(0u64, |total, part| part.parse::<u64>().ok().map(|number| total * 60 + number))?;
+    Some(minutes as f64 * 60.0 + seconds)
+}
+
+/// whisper 失败时的错误信息。指定了 GPU 设备而本机不支持时，PyTorch 的报错埋在很长的堆栈里，单独提示出来
+fn describe_whisper_failure(stderr: &str, options: &TranscribeOptions) -> String {
+    let device_error = match options.device.as_deref() {
+        Some("cuda") => stderr.lines().rev().find(|line| line.contains("CUDA") || line.contains("cuda")),
+        Some("mps") => stderr.lines().rev().find(|line| line.contains("MPS") || line.contains("mps")),
+        _ => None,
+    };
+    match (device_error, options.device.as_deref()) {
+        (Some(line), Some(device)) => format!("无法使用 {} 设备转录: {}。请检查显卡驱动和 PyTorch 是否支持 {}，或将 device 改为 cpu", device, line.trim(), device),
+        _ => format!("Whisper 转录失败: {}", stderr),
+    }
+}
+
+/// 删除被终止的转录进程可能留下的输出文件
+fn remove_partial_transcription(audio_file_path: &str, options: &TranscribeOptions) {
+    for format in ["txt", "srt", "vtt", "json", "tsv"] {
+        let path = options.output_file(audio_file_path, format);
+        if path.is_file() {
+            if let Err(e) = fs::remove_file(&path) {
+                tracing::warn!("清理未完成的转录文件失败: {}", e);
+            }
+        }
+    }
+}
+
+const FFMPEG_BINARY: &str = "ffmpeg";
+/// 分段文件所在的子目录，位于转录输出目录下
+const TRANSCRIBE_CHUNKS_DIR: &str = "chunks";
+/// 切分完成的标记文件，没有它说明上次切分被中断，需要重新切分
+const CHUNKS_COMPLETE_MARKER: &str = ".complete";
+
+/// 用 ffmpeg 把长音频切成固定时长的片段逐段转录。每段完成后通过 `on_chunk_done` 保存进度，
+/// `resume` 中已完成的段会被跳过；最后合并文本和时间戳（加上各段偏移），按需生成字幕文件。
+async fn transcribe_audio_in_chunks<Fut>(
+    audio_file_path: &str,
+    options: &TranscribeOptions,
+    chunk_seconds: u64,
+    resume: Option<ChunkedTranscription>,
+    mut on_chunk_done: impl FnMut(&ChunkedTranscription) -> Fut,
+    progress: &ProgressEmitter,
+    cancel: &CancelToken,
+) -> Result<TranscriptionOutput, PipelineError>
+where
+    Fut: std::future::Future<Output = Result<(), PipelineError>>,
+{
+    options.validate()?;
+    
+    let audio_path = Path::new(audio_file_path);
+    let chunks_dir = options.output_dir(audio_file_path).join(TRANSCRIBE_CHUNKS_DIR);
+    let chunk_files = split_audio_into_chunks(audio_path, &chunks_dir, chunk_seconds, cancel).await?;
+    
+    let mut state = resume.unwrap_or(ChunkedTranscription { chunk_seconds, chunks: Vec::new() });
+    state.chunks.retain(|chunk| chunk.index < chunk_files.len());
+    
+    // 每段只需要文本和时间戳，字幕在合并后统一生成。各段单独识别的说话人编号无法对应，分段转录时不识别说话人
+    let mut warnings = Vec::new();
+    if options.diarize {
+        let warning = "分段转录时各段的说话人无法对应，已跳过说话人识别".to_string();
+        progress.emit("transcribe", None, &warning);
+        warnings.push(warning);
+    }
+    let chunk_options = TranscribeOptions {
+        subtitle_formats: Vec::new(),
+        include_segments: true,
+        // 各段合并后再整理段落
+        format_paragraphs: false,
+        chunk_seconds: None,
+        diarize: false,
+        // 各段的输出写在分段目录中，与分段音频放在一起
+        output_dir: None,
+        ..options.clone()
+    };
+    
+    let total = chunk_files.len();
+    for (index, chunk_file) in chunk_files.iter().enumerate() {
+        if state.chunks.iter().any(|chunk| chunk.index == index) {
+            continue;
+        }
+        check_cancelled(cancel)?;
+        progress.emit("transcribe", Some(state.chunks.len() as f32 / total as f32 * 100.0), &format!("正在转录第 {}/{} 段", index + 1, total));
+        
+        let offset = (index as u64 * chunk_seconds) as f64;
+        let on_segment = |segment: &TranscriptSegment| progress.emit_transcript_chunk(&TranscriptSegment {
+            start: segment.start + offset,
+            end: segment.end + offset,
+            ..segment.clone()
+        });
+        let output = transcribe_audio_file(chunk_file, &chunk_options, progress, on_segment, cancel).await?;
+        state.chunks.push(ChunkTranscript {
+            index,
+            text: output.content,
+            segments: output.segments.unwrap_or_default().into_iter()
+                .map(|segment| TranscriptSegment {
+                    start: segment.start + offset,
+                    end: segment.end + offset,
+                    ..segment
+                })
+                .collect(),
+            detected_language: output.detected_language,
+        });
+        state.chunks.sort_by_key(|chunk| chunk.index);
+        on_chunk_done(&state).await?;
+    }
+    
+    let content = state.chunks.iter()
+        .map(|chunk| chunk.text.trim())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    // 各段分别检测语言，以第一个检测到的为准
+    let detected_language = state.chunks.iter()
+        .find_map(|chunk| chunk.detected_language.clone());
+    let segments: Vec<TranscriptSegment> = state.chunks.into_iter()
+        .flat_map(|chunk| chunk.segments)
+        .collect();
+    
+    // 与不分段时一样，在输出目录写出 `<主文件名>.txt` 及请求的字幕文件
+    let write_output = |format: &str, body: String| -> Result<String, PipelineError> {
+        let path = options.output_file(audio_file_path, format);
+        fs::write(&path, body)
+            .map_err(|e| PipelineError::TranscriptionFailed(format!("写入 {} 文件失败: {}", format, e)))?;
+        Ok(path.to_string_lossy().to_string())
+    };
+    let transcript_file = write_output("txt", format!("{}\n", content))?;
+    let content = if options.format_paragraphs { format_transcript(&content) } else { content };
+    let mut subtitle_files = HashMap::new();
+    for format in &options.subtitle_formats {
+        let body = if format == "vtt" { render_vtt(&segments) } else { render_srt(&segments) };
+        subtitle_files.insert(format.clone(), write_output(format, body)?);
+    }
+    
+    if let Err(e) = fs::remove_dir_all(&chunks_dir) {
+        tracing::warn!("清理分段音频失败: {}", e);
+    }
+    
+    Ok(TranscriptionOutput {
+        content,
+        transcript_file,
+        subtitle_files,
+        segments: options.include_segments.then_some(segments),
+        detected_language,
+        warnings,
+    })
+}
+
+/// 把音频切成 16kHz 单声道 wav 片段（两种转录后端都能直接读取），已完整切分过则直接复用
+async fn split_audio_into_chunks(audio_path: &Path, chunks_dir: &Path, chunk_seconds: u64, cancel: &CancelToken) -> Result<Vec<String>, PipelineError> {
+    let list_chunks = || -> Result<Vec<String>, PipelineError> {
+        let mut files: Vec<String> = fs::read_dir(chunks_dir)
+            .map_err(|e| PipelineError::TranscriptionFailed(format!("读取分段目录失败: {}", e)))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "wav"))
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        // 文件名带零填充序号，按名称排序即按时间顺序
+        files.sort();
+        Ok(files)
+    };
+    
+    if chunks_dir.join(CHUNKS_COMPLETE_MARKER).is_file() {
+        return list_chunks();
+    }
+    
+    match probe_tool_version(FFMPEG_BINARY, "-version").await {
+        Ok(Some(_)) => {}
+        Err(e @ PipelineError::Timeout(_)) => return Err(e),
+        _ => return Err(PipelineError::ToolNotFound("分段转录需要 ffmpeg，请先安装 ffmpeg 并确保其在 PATH 中".to_string())),
+    }
+    
+    if chunks_dir.exists() {
+        fs::remove_dir_all(chunks_dir)
+            .map_err(|e| PipelineError::TranscriptionFailed(format!("清理未完成的分段失败: {}", e)))?;
+    }
+    fs::create_dir_all(chunks_dir)
+        .map_err(|e| PipelineError::TranscriptionFailed(format!("创建分段目录失败: {}", e)))?;
+    
+    let mut command = tokio::process::Command::new(FFMPEG_BINARY);
+    command
+        .arg("-hide_banner")
+        .arg("-loglevel").arg("error")
+        .arg("-i").arg(audio_path)
+        .arg("-f").arg("segment")
+        .arg("-segment_time").arg(chunk_seconds.to_string())
+        .arg("-ar").arg("16000")
+        .arg("-ac").arg("1")
+        .arg("-c:a").arg("pcm_s16le")
+        .arg(chunks_dir.join("chunk_%04d.wav"));
+    
+    let output = run_streaming(command, cancel, None, |_| {}).await;
+    match output {
+        Ok(result) if result.status.success() => {}
+        Ok(result) => return Err(PipelineError::TranscriptionFailed(format!("ffmpeg 切分音频失败: {}", result.stderr.trim()))),
+        Err(_) if cancel.is_cancelled() => return Err(PipelineError::Cancelled),
+        Err(e) => return Err(PipelineError::TranscriptionFailed(format!("执行 ffmpeg 失败: {}", e))),
+    }
+    
+    fs::write(chunks_dir.join(CHUNKS_COMPLETE_MARKER), "")
+        .map_err(|e| PipelineError::TranscriptionFailed(format!("写入分段标记失败: {}", e)))?;
+    list_chunks()
+}
+
+/// 秒数格式化为字幕时间戳，srt 用逗号分隔毫秒，vtt 用点
+fn format_subtitle_timestamp(seconds: f64, millis_separator: char) -> String {
+    let total_millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        total_millis / 3_600_000,
+        total_millis / 60_000 % 60,
+        total_millis / 1000 % 60,
+        millis_separator,
+        total_millis % 1000
+    )
+}
+
+fn render_srt(segments: &[TranscriptSegment]) -> String {
+    segments.iter()
+        .enumerate()
+        .map(|(index, segment)| format!(
+            "{}\n{} --> {}\n{}\n",
+            index + 1,
+            format_subtitle_timestamp(segment.start, ','),
+            format_subtitle_timestamp(segment.end, ','),
+            segment.text.trim()
+        ))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn render_vtt(segments: &[TranscriptSegment]) -> String {
+    let cues = segments.iter()
+        .map(|segment| format!(
+            "{} --> {}\n{}\n",
+            format_subtitle_timestamp(segment.start, '.'),
+            format_subtitle_timestamp(segment.end, '.'),
+            segment.text.trim()
+        ))
+        .collect::<Vec<String>>()
+        .join("\n");
+    format!("WEBVTT\n\n{}", cues)
+}
+
+fn build_python_whisper_command(binary: &str, audio_file_path: &str, options: &TranscribeOptions) -> tokio::process::Command {
+    // 使用 whisper 命令行工具进行转录
+    let mut command = tokio::process::Command::new(binary);
+    command
+        .arg(audio_file_path)
+        .arg("--model").arg(&options.model)
+        // 只需要纯文本时输出 txt，需要字幕或分段时用 all 一次生成包括 txt/srt/vtt/json 在内的全部格式
+        .arg("--output_format").arg(if options.subtitle_formats.is_empty() && !options.needs_json() { "txt" } else { "all" })
+        .arg("--output_dir").arg(options.output_dir(audio_file_path))
+        .arg("--task").arg(&options.task)
+        .env("PYTHONUNBUFFERED", "1");  // 关闭 Python 输出缓冲，使转录片段实时输出
+    force_utf8_output(&mut command);
+    
+    // 指定语言可跳过 whisper 的自动语言检测
+    if let Some(language) = &options.language {
+        command.arg("--language").arg(language);
+    }
+    if let Some(device) = &options.device {
+        command.arg("--device").arg(device);
+    }
+    if let Some(fp16) = options.fp16 {
+        command.arg("--fp16").arg(if fp16 { "True" } else { "False" });
+    }
+    
+    command
+}
+
+/// 检查说话人识别所需的 whisperx 和 Hugging Face 令牌，不满足时返回原因
+fn diarization_token(options: &TranscribeOptions) -> Result<&str, String> {
+    if find_in_path(WHISPERX_BINARY).is_none() {
+        return Err("未找到 whisperx（pip install whisperx）".to_string());
+    }
+    options.hf_token.as_deref()
+        .ok_or_else(|| format!("未配置 Hugging Face 令牌（hf_token 或环境变量 {}）", HF_TOKEN_ENV))
+}
+
+fn build_whisperx_command(audio_file_path: &str, options: &TranscribeOptions, hf_token: &str) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(WHISPERX_BINARY);
+    command
+        .arg(audio_file_path)
+        .arg("--model").arg(&options.model)
+        .arg("--output_format").arg("all")
+        .arg("--output_dir").arg(options.output_dir(audio_file_path))
+        .arg("--task").arg(&options.task)
+        .arg("--diarize")
+        .arg("--hf_token").arg(hf_token)
+        .env("PYTHONUNBUFFERED", "1");
+    force_utf8_output(&mut command);
+    
+    if let Some(language) = &options.language {
+        command.arg("--language").arg(language);
+    }
+    if let Some(device) = &options.device {
+        command.arg("--device").arg(device);
+    }
+    // whisperx 用 compute_type 代替 fp16 开关
+    if let Some(fp16) = options.fp16 {
+        command.arg("--compute_type").arg(if fp16 { "float16" } else { "float32" });
+    }
+    
+    command
+}
+
+fn build_whisper_cpp_command(binary: &str, audio_file_path: &str, options: &TranscribeOptions) -> Result<tokio::process::Command, PipelineError> {
+    let model_path = options.whisper_cpp_model.as_ref()
+        .ok_or_else(|| PipelineError::InvalidInput("使用 whisper.cpp 转录需要指定 ggml 模型文件路径 (whisper_cpp_model)".to_string()))?;
+    if !Path::new(model_path).is_file() {
+        return Err(PipelineError::InvalidInput(format!("whisper.cpp 模型文件不存在: {}", model_path)));
+    }
+    
+    // whisper.cpp 默认输出为 `<音频文件名>.txt`，用 -of 指定为与 Python 版一致的 `<主文件名>.txt`
+    let audio_path = Path::new(audio_file_path);
+    let output_base = options.output_dir(audio_file_path).join(audio_path.file_stem().unwrap_or_default());
+    
+    let mut command = tokio::process::Command::new(binary);
+    command
+        .arg("-m").arg(model_path)
+        .arg("-f").arg(audio_file_path)
+        .arg("-otxt")
+        .arg("-of").arg(&output_base)
+        // whisper.cpp 默认按英文识别，未指定语言时需要显式开启自动检测
+        .arg("-l").arg(options.language.as_deref().unwrap_or("auto"));
+    
+    // whisper.cpp 编译时启用了 GPU 就会使用，指定 cpu 时关闭
+    if options.device.as_deref() == Some("cpu") {
+        command.arg("-ng");
+    }
+    if options.task == WHISPER_TASK_TRANSLATE {
+        command.arg("-tr");
+    }
+    
+    for format in &options.subtitle_formats {
+        command.arg(format!("-o{}", format));
+    }
+    if options.needs_json() {
+        command.arg("-oj");
+    }
+    
+    Ok(command)
+}
+
+/// Python 版 whisper 的 json 输出：`segments` 中的时间单位为秒，`language` 为识别时使用或检测到的语言
+#[derive(Deserialize)]
+struct WhisperJsonOutput {
+    segments: Vec<TranscriptSegment>,
+    #[serde(default)]
+    language: Option<String>,
+}
+
+/// whisper.cpp 的 json 输出：`transcription[].offsets` 的单位为毫秒，语言在 `result.language`
+#[derive(Deserialize)]
+struct WhisperCppJsonOutput {
+    transcription: Vec<WhisperCppSegment>,
+    #[serde(default)]
+    result: Option<WhisperCppResult>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppResult {
+    #[serde(default)]
+    language: Option<String>,
+}
+
+/// 从 whisper 的 json 输出中解析出的分段和语言
+struct WhisperJson {
+    segments: Vec<TranscriptSegment>,
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppSegment {
+    offsets: WhisperCppOffsets,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct WhisperCppOffsets {
+    from: u64,
+    to: u64,
+}
+
+fn parse_whisper_json(backend: TranscriptionBackend, body: &str) -> Result<WhisperJson, String> {
+    let parsed = match backend {
+        TranscriptionBackend::PythonWhisper => serde_json::from_str::<WhisperJsonOutput>(body)
+            .map(|output| WhisperJson {
+                segments: output.segments,
+                language: output.language,
+            }),
+        TranscriptionBackend::WhisperCpp => serde_json::from_str::<WhisperCppJsonOutput>(body)
+            .map(|output| WhisperJson {
+                segments: output.transcription.into_iter()
+                    .map(|segment| TranscriptSegment {
+                        start: segment.offsets.from as f64 / 1000.0,
+                        end: segment.offsets.to as f64 / 1000.0,
+                        text: segment.text,
+                        speaker: None,
+                        avg_logprob: None,
+                        no_speech_prob: None,
+                        low_confidence: false,
+                    })
+                    .collect(),
+                language: output.result.and_then(|result| result.language),
+            }),
+    }.map_err(|e| format!("解析 json 转录结果失败: {}", e))?;
+    
+    Ok(WhisperJson {
+        language: parsed.language
+            .map(|language| language.trim().to_lowercase())
+            .filter(|language| !language.is_empty()),
+        ..parsed
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    max_tokens: u32,
+    temperature: f32,
+}
+
+#[derive(Clone)]
+enum ApiProvider {
+    OpenAI,
+    DeepSeek,
+    Anthropic,
+    Gemini,
+    /// OpenAI 兼容的自建服务（Ollama、LM Studio 等），base_url 为完整的 chat/completions 地址
+    Custom { base_url: String, model: String },
+}
+
+impl ApiProvider {
+    fn from_options(options: &PipelineOptions) -> Result<Self, PipelineError> {
+        match options.api_provider.as_deref() {
+            Some("deepseek") => Ok(ApiProvider::DeepSeek),
+            Some("anthropic") => Ok(ApiProvider::Anthropic),
+            Some("gemini") => Ok(ApiProvider::Gemini),
+            Some("custom") => {
+                let base_url = options.custom_base_url.as_deref()
+                    .map(|url| url.trim().trim_end_matches('/'))
+                    .filter(|url| !url.is_empty())
+                    .ok_or_else(|| PipelineError::InvalidInput("使用自定义 API 需要指定 custom_base_url，如 http://localhost:11434/v1".to_string()))?;
+                let model = options.custom_model.as_deref()
+                    .map(str::trim)
+                    .filter(|model| !model.is_empty())
+                    .ok_or_else(|| PipelineError::InvalidInput("使用自定义 API 需要指定模型名称 (custom_model)".to_string()))?;
+                reqwest::Url::parse(base_url)
+                    .map_err(|e| PipelineError::InvalidInput(format!("无效的自定义 API 地址 {}: {}", base_url, e)))?;
+                
+                let base_url = if base_url.ends_with("/chat/completions") {
+                    base_url.to_string()
+                } else {
+                    format!("{}/chat/completions", base_url)
+                };
+                Ok(ApiProvider::Custom { base_url, model: model.to_string() })
+            }
+            _ => Ok(ApiProvider::OpenAI),
+        }
+    }
+    
+    /// 记录在 VideoRecord 中的服务名称，与前端的 api_provider 取值一致
+    fn name(&self) -> &str {
+        match self {
+            ApiProvider::OpenAI => "openai",
+            ApiProvider::DeepSeek => "deepseek",
+            ApiProvider::Anthropic => "anthropic",
+            ApiProvider::Gemini => "gemini",
+            ApiProvider::Custom { .. } => "custom",
+        }
+    }
+    
+    /// 本地服务通常不需要 API 密钥
+    fn requires_api_key(&self) -> bool {
+        !matches!(self, ApiProvider::Custom { .. })
+    }
+    
+    /// Gemini 的模型名称是接口地址的一部分
+    fn endpoint(&self, model: &str) -> String {
+        match self {
+            ApiProvider::OpenAI => "https://api.openai.com/v1/chat/completions".to_string(),
+            ApiProvider::DeepSeek => "https://api.deepseek.com/chat/completions".to_string(),
+            ApiProvider::Anthropic => "https://api.anthropic.com/v1/messages".to_string(),
+            ApiProvider::Gemini => format!("https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent", model),
+            ApiProvider::Custom { base_url, .. } => base_url.clone(),
+        }
+    }
+    
+    /// 列出模型的接口，只需要密钥、不消耗 token，用于检查密钥是否有效
+    fn models_endpoint(&self) -> String {
+        match self {
+            ApiProvider::OpenAI => "https://api.openai.com/v1/models".to_string(),
+            ApiProvider::DeepSeek => "https://api.deepseek.com/models".to_string(),
+            ApiProvider::Anthropic => "https://api.anthropic.com/v1/models".to_string(),
+            ApiProvider::Gemini => "https://generativelanguage.googleapis.com/v1beta/models".to_string(),
+            ApiProvider::Custom { base_url, .. } => format!("{}/models", base_url.trim_end_matches("/chat/completions")),
+        }
+    }
+    
+    fn default_model(&self) -> &str {
+        match self {
+            ApiProvider::OpenAI => "gpt-3.5-turbo",
+            ApiProvider::DeepSeek => "deepseek-chat",
+            ApiProvider::Anthropic => "claude-3-5-sonnet-latest",
+            ApiProvider::Gemini => "gemini-1.5-flash",
+            ApiProvider::Custom { model, .. } => model,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+}
+
+const ANTHROPIC_API_VERSION: &str = "2023-06-01";
+
+/// Anthropic Messages API 的请求体，system 提示词是顶层字段而不是一条消息
+#[derive(Serialize)]
+struct AnthropicRequest {
+    model: String,
+    system: String,
+    messages: Vec<ChatMessage>,
+    max_tokens: u32,
+    temperature: f32,
+}
+
+#[derive(Deserialize)]
+struct AnthropicContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicResponse {
+    content: Vec<AnthropicContentBlock>,
+}
+
+/// Gemini generateContent 的请求体，模型由 URL 指定，API 密钥通过 `?key=` 查询参数传递
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    system_instruction: GeminiContent,
+    contents: Vec<GeminiContent>,
+    generation_config: GeminiGenerationConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GeminiContent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GeminiPart {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig {
+    max_output_tokens: u32,
+    temperature: f32,
+}
+
+#[derive(Deserialize)]
+struct GeminiCandidate {
+    #[serde(default)]
+    content: Option<GeminiContent>,
+}
+
+#[derive(Deserialize)]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+}
+
+impl GeminiContent {
+    fn text(role: Option<&str>, text: &str) -> Self {
+        GeminiContent {
+            role: role.map(|role| role.to_string()),
+            parts: vec![GeminiPart { text: Some(text.to_string()) }],
+        }
+    }
+}
+
+/// 调用 LLM 接口失败的原因。网络错误和重试后仍被限流属于暂时不可用，回退到简单总结；
+/// 密钥错误和接口返回的其他错误直接上报，让用户修正配置
+enum CompletionError {
+    Network(String),
+    /// 429/503 重试多次后仍失败
+    RateLimited(String),
+    Auth(String),
+    Api(String),
+}
+
+/// 接口返回 429/503 时最多重试的次数
+const COMPLETION_MAX_RETRIES: u32 = 2;
+/// 响应没有 Retry-After 时首次重试前等待的秒数，之后每次翻倍
+const COMPLETION_RETRY_BASE_SECONDS: u64 = 2;
+/// Retry-After 要求等待更久时不再重试
+const COMPLETION_MAX_RETRY_WAIT_SECONDS: u64 = 60;
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+}
+
+/// 读取 Retry-After 头中的秒数；HTTP 日期格式的值不解析，按默认退避处理
+fn retry_after_seconds(response: &reqwest::Response) -> Option<u64> {
+    response.headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str().ok()?
+        .trim()
+        .parse().ok()
+}
+
+fn parse_chat_completion_response(body: &str) -> Result<String, String> {
+    let chat_response = serde_json::from_str::<ChatCompletionResponse>(body)
+        .map_err(|e| format!("解析API响应失败: {}", e))?;
+    
+    chat_response.choices.into_iter().next()
+        .map(|choice| choice.message.content)
+        .ok_or_else(|| "API返回了空的总结结果".to_string())
+}
+
+fn parse_anthropic_response(body: &str) -> Result<String, String> {
+    let response = serde_json::from_str::<AnthropicResponse>(body)
+        .map_err(|e| format!("解析API响应失败: {}", e))?;
+    
+    let text = response.content.into_iter()
+        .filter(|block| block.kind == "text")
+        .filter_map(|block| block.text)
+        .collect::<Vec<String>>()
+        .join("");
+    
+    if text.trim().is_empty() {
+        Err("API返回了空的总结结果".to_string())
+    } else {
+        Ok(text)
+    }
+}
+
+fn parse_gemini_response(body: &str) -> Result<String, String> {
+    let response = serde_json::from_str::<GeminiResponse>(body)
+        .map_err(|e| format!("解析API响应失败: {}", e))?;
+    
+    let text = response.candidates.into_iter().next()
+        .and_then(|candidate| candidate.content)
+        .map(|content| content.parts.into_iter()
+            .filter_map(|part| part.text)
+            .collect::<Vec<String>>()
+            .join(""))
+        .unwrap_or_default();
+    
+    if text.trim().is_empty() {
+        Err("API返回了空的总结结果".to_string())
+    } else {
+        Ok(text)
+    }
+}
+
+async fn request_completion(client: &reqwest::Client, options: &SummaryOptions, system_prompt: &str, user_prompt: &str) -> Result<String, CompletionError> {
+    let provider = &options.provider;
+    let api_key = options.api_key.as_deref().unwrap_or_default();
+    let model = options.model();
+    let request = match provider {
+        ApiProvider::Anthropic => client
+            .post(provider.endpoint(model))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_API_VERSION)
+            .json(&AnthropicRequest {
+                model: model.to_string(),
+                system: system_prompt.to_string(),
+                messages: vec![ChatMessage {
+                    role: "user".to_string(),
+                    content: user_prompt.to_string(),
+                }],
+                max_tokens: options.max_tokens,
+                temperature: options.temperature,
+            }),
+        ApiProvider::Gemini => client
+            .post(provider.endpoint(model))
+            .query(&[("key", api_key)])
+            .json(&GeminiRequest {
+                system_instruction: GeminiContent::text(None, system_prompt),
+                contents: vec![GeminiContent::text(Some("user"), user_prompt)],
+                generation_config: GeminiGenerationConfig {
+                    max_output_tokens: options.max_tokens,
+                    temperature: options.temperature,
+                },
+            }),
+        ApiProvider::OpenAI | ApiProvider::DeepSeek | ApiProvider::Custom { .. } => {
+            let mut builder = client.post(provider.endpoint(model));
+            if !api_key.is_empty() {
+                builder = builder.header("Authorization", format!("Bearer {}", api_key));
+            }
+            builder.json(&ChatCompletionRequest {
+                model: model.to_string(),
+                messages: vec![
+                    ChatMessage {
+                        role: "system".to_string(),
+                        content: system_prompt.to_string(),
+                    },
+                    ChatMessage {
+                        role: "user".to_string(),
+                        content: user_prompt.to_string(),
+                    },
+                ],
+                max_tokens: options.max_tokens,
+                temperature: options.temperature,
+            })
+        }
+    };
+    
+    let request = request.header("Content-Type", "application/json");
+    let mut retries = 0;
+    let response = loop {
+        // 请求体是 JSON，总能复制
+        let attempt = request.try_clone()
+            .ok_or_else(|| CompletionError::Api("无法构造API请求".to_string()))?;
+        let response = attempt.send()
+            .await
+            .map_err(|e| CompletionError::Network(e.to_string()))?;
+        
+        let status = response.status();
+        if !is_retryable_status(status) {
+            break response;
+        }
+        if retries >= COMPLETION_MAX_RETRIES {
+            return Err(CompletionError::RateLimited(format!("API请求过于频繁或服务暂不可用，重试 {} 次后仍失败，状态码: {}", retries, status)));
+        }
+        let wait = retry_after_seconds(&response)
+            .unwrap_or(COMPLETION_RETRY_BASE_SECONDS << retries);
+        if wait > COMPLETION_MAX_RETRY_WAIT_SECONDS {
+            return Err(CompletionError::RateLimited(format!("API要求 {} 秒后重试，状态码: {}", wait, status)));
+        }
+        retries += 1;
+        tracing::warn!("API返回 {}，{} 秒后第 {} 次重试", status, wait, retries);
+        tokio::time::sleep(tokio::time::Duration::from_secs(wait)).await;
+    };
+    
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(CompletionError::Auth(format!("API密钥无效或无权限，状态码: {}", status)));
+    }
+    if !status.is_success() {
+        return Err(CompletionError::Api(format!("API请求失败，状态码: {}", status)));
+    }
+    
+    let body = response.text().await
+        .map_err(|e| CompletionError::Api(format!("解析API响应失败: {}", e)))?;
+    
+    match provider {
+        ApiProvider::Anthropic => parse_anthropic_response(&body),
+        ApiProvider::Gemini => parse_gemini_response(&body),
+        ApiProvider::OpenAI | ApiProvider::DeepSeek | ApiProvider::Custom { .. } => parse_chat_completion_response(&body),
+    }.map_err(CompletionError::Api)
+}
+
+/// 未使用自定义提示词时的系统提示词模板，`{style}` 替换为总结风格的要求，`{language}` 替换为回复语言
+const SUMMARY_SYSTEM_PROMPT_TEMPLATE: &str = "你是一个专业的内容总结助手。{style}请用{language}回复。";
+const DEFAULT_SUMMARY_LANGUAGE: &str = "中文";
+
+/// 预设的总结风格，比直接编辑提示词更容易上手，可以与 summary_language 组合使用
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum SummaryStyle {
+    /// 概括主要观点、重要信息和关键结论
+    #[default]
+    General,
+    /// 分条列出要点
+    Bullets,
+    /// 一段话的简短概要
+    TldrOneParagraph,
+    /// 按内容顺序的详细总结
+    Detailed,
+    /// 摘录视频中的关键原话
+    KeyQuotes,
+}
+
+impl SummaryStyle {
+    /// 系统提示词中对总结内容和格式的要求
+    fn instruction(self) -> &'static str {
+        match self {
+            SummaryStyle::General => "请为用户提供简洁、准确的视频内容总结。总结应该包含主要观点、重要信息和关键结论。",
+            SummaryStyle::Bullets => "请用 5-10 条要点总结视频内容，每条以「- 」开头、只写一句话，不要输出标题或其他说明。",
+            SummaryStyle::TldrOneParagraph => "请用一段不超过 100 字的话概括视频的核心内容，不要分点或分段。",
+            SummaryStyle::Detailed => "请按视频内容的先后顺序给出详细总结，分小节说明每部分的主要观点、论据、例子和结论，保留重要的数字和名称。",
+            SummaryStyle::KeyQuotes => "请从转录内容中摘录 5-10 句最能体现视频观点的原话，每句单独一行并加上引号，在下一行用一句话说明它的意思。",
+        }
+    }
+    
+    /// 未指定 max_tokens 时的默认值，详细总结和摘录需要更长的输出
+    fn max_tokens(self) -> u32 {
+        match self {
+            SummaryStyle::General | SummaryStyle::Bullets => DEFAULT_SUMMARY_MAX_TOKENS,
+            SummaryStyle::TldrOneParagraph => 250,
+            SummaryStyle::KeyQuotes => 1000,
+            SummaryStyle::Detailed => 1500,
+        }
+    }
+}
+
+/// 额外语言的提示词：自定义提示词后追加回复语言的要求，否则按风格套用模板
+fn build_language_summary_prompt(summary_prompt: Option<&str>, style: SummaryStyle, language: &str) -> String {
+    match summary_prompt.map(str::trim).filter(|prompt| !prompt.is_empty()) {
+        Some(prompt) => format!("{}\n请用{}回复。", prompt, language),
+        None => SUMMARY_SYSTEM_PROMPT_TEMPLATE
+            .replace("{style}", style.instruction())
+            .replace("{language}", language),
+    }
+}
+
+/// 自定义提示词优先；否则按风格和语言套用模板，未指定语言时用中文回复
+fn build_summary_system_prompt(summary_prompt: Option<&str>, style: SummaryStyle, summary_language: Option<&str>) -> String {
+    if let Some(prompt) = summary_prompt.map(str::trim).filter(|prompt| !prompt.is_empty()) {
+        return prompt.to_string();
+    }
+    let language = summary_language.map(str::trim)
+        .filter(|language| !language.is_empty())
+        .unwrap_or(DEFAULT_SUMMARY_LANGUAGE);
+    build_language_summary_prompt(None, style, language)
+}
+
+/// 单次总结请求的转录文本长度上限（字符数），超出后分段总结
+const DEFAULT_SUMMARY_CHUNK_CHARS: usize = 12000;
+/// 相邻分段之间重叠的字符数，避免在分段边界处丢失上下文
+const SUMMARY_CHUNK_OVERLAP_CHARS: usize = 500;
+
+/// 未调用 API、由 generate_simple_summary 生成总结时记录的服务名称
+const SIMPLE_SUMMARY_PROVIDER: &str = "simple";
+const READING_WORDS_PER_MINUTE: usize = 200;
+
+/// 总结文本以及转录内容的统计信息
+struct SummaryResult {
+    text: String,
+    word_count: usize,
+    reading_minutes: u32,
+    provider: String,
+    /// 调用 API 时使用的模型
+    model: Option<String>,
+    /// 调用 API 时使用的预设风格
+    style: Option<SummaryStyle>,
+    keywords: Vec<String>,
+    /// 多语言总结时按语言保存的各份总结，包括主语言
+    summaries: Option<HashMap<String, String>>,
+    /// 部分语言生成失败等不影响主语言总结的问题
+    warnings: Vec<String>,
+}
+
+impl SummaryResult {
+    /// 关键词默认按词频提取，调用 API 成功时再替换为模型给出的结果
+    fn new(text: String, transcript: &str, provider: &str) -> Self {
+        let word_count = count_words(transcript);
+        SummaryResult {
+            text,
+            word_count,
+            reading_minutes: word_count.div_ceil(READING_WORDS_PER_MINUTE) as u32,
+            provider: provider.to_string(),
+            model: None,
+            style: None,
+            keywords: extract_keywords_by_frequency(transcript, MAX_KEYWORDS),
+            summaries: None,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+const MAX_KEYWORDS: usize = 10;
+const KEYWORD_SYSTEM_PROMPT: &str = "你是一个内容标签助手。只输出一个 JSON 字符串数组，不要输出任何其他内容。";
+
+/// 根据总结让模型给出 5-10 个关键词
+async fn request_keywords(client: &reqwest::Client, options: &SummaryOptions, summary: &str) -> Result<Vec<String>, CompletionError> {
+    let user_prompt = format!(
+        "请为以下视频内容总结提取 5 到 10 个关键词或主题标签，使用与总结相同的语言，以 JSON 字符串数组返回，例如 [\"关键词1\", \"关键词2\"]：\n\n{}",
+        summary
+    );
+    let response = request_completion(client, options, KEYWORD_SYSTEM_PROMPT, &user_prompt).await?;
+    parse_keywords_response(&response).map_err(CompletionError::Api)
+}
+
+/// 从模型回复中取出 JSON 数组，兼容包在 Markdown 代码块或前后带说明文字的情况
+fn parse_keywords_response(response: &str) -> Result<Vec<String>, String> {
+    let start = response.find('[');
+    let end = response.rfind(']');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => return Err(format!("关键词结果不是 JSON 数组: {}", response.trim())),
+    };
+    let keywords: Vec<String> = serde_json::from_str(json)
+        .map_err(|e| format!("解析关键词失败: {}", e))?;
+    
+    let mut seen = HashSet::new();
+    let keywords: Vec<String> = keywords.into_iter()
+        .map(|keyword| keyword.trim().to_string())
+        .filter(|keyword| !keyword.is_empty() && seen.insert(keyword.to_lowercase()))
+        .take(MAX_KEYWORDS)
+        .collect();
+    if keywords.is_empty() {
+        Err("API返回了空的关键词列表".to_string())
+    } else {
+        Ok(keywords)
+    }
+}
+
+/// 英文中常见的虚词，不作为关键词
+const KEYWORD_STOPWORDS: [&str; 39] = [
+    "the", "and", "for", "are", "but", "not", "you", "all", "can", "had", "her", "was", "one", "our", "out",
+    "has", "have", "this", "that", "with", "from", "they", "will", "would", "there", "their", "what", "about",
+    "which", "when", "your", "just", "like", "into", "then", "them", "than", "some", "because",
+];
+/// 中文里常见的助词、代词，包含这些字的双字组合不作为关键词
+const KEYWORD_CJK_STOP_CHARS: [char; 20] = ['的', '了', '是', '在', '我', '你', '他', '她', '它', '这', '那', '和', '就', '也', '都', '不', '有', '个', '们', '吗'];
+
+/// 没有 API 时按词频提取关键词，至少出现两次才算关键词
+fn extract_keywords_by_frequency(text: &str, limit: usize) -> Vec<String> {
+    // 词 -> (出现次数, 首次出现的顺序)，次数相同时先出现的排在前面
+    let mut counts: HashMap<String, (usize, usize)> = HashMap::new();
+    for term in keyword_terms(text) {
+        let order = counts.len();
+        counts.entry(term).or_insert((0, order)).0 += 1;
+    }
+    
+    let mut terms: Vec<(String, (usize, usize))> = counts.into_iter()
+        .filter(|(_, (count, _))| *count >= 2)
+        .collect();
+    terms.sort_by_key(|(_, (count, order))| (std::cmp::Reverse(*count), *order));
+    terms.into_iter()
+        .take(limit)
+        .map(|(term, _)| term)
+        .collect()
+}
+
+/// 按出现顺序切出参与词频统计的词：空格分隔的文字按单词（跳过短词、纯数字和虚词），
+/// 中日韩文字没有分词，按相邻两个字切分
+fn keyword_terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+    // 末尾补一个空格，让最后一个词和最后一段中日韩文字也被统计
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if is_cjk_char(c) {
+            cjk_run.push(c);
+        } else {
+            for pair in cjk_run.windows(2) {
+                if !pair.iter().any(|c| KEYWORD_CJK_STOP_CHARS.contains(c)) {
+                    terms.push(pair.iter().collect());
+                }
+            }
+            cjk_run.clear();
+        }
+        
+        if c.is_alphanumeric() && !is_cjk_char(c) {
+            word.extend(c.to_lowercase());
+        } else if !word.is_empty() {
+            let term = std::mem::take(&mut word);
+            if term.chars().count() >= 3 && !term.chars().all(|c| c.is_ascii_digit()) && !KEYWORD_STOPWORDS.contains(&term.as_str()) {
+                terms.push(term);
+            }
+        }
+    }
+    terms
+}
+
+/// 统计词数：空白分隔的每个词算一个；中日韩、泰文等不用空格分词的文字每个字单独计数
+fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|token| {
+            let unspaced_chars = token.chars().filter(|c| is_unspaced_script_char(*c)).count();
+            let has_other_text = token.chars().any(|c| c.is_alphanumeric() && !is_unspaced_script_char(c));
+            unspaced_chars + usize::from(has_other_text)
+        })
+        .sum()
+}
+
+/// 书写时词与词之间不加空格的文字
+fn is_unspaced_script_char(c: char) -> bool {
+    is_cjk_char(c) || matches!(c,
+        '\u{0E00}'..='\u{0EFF}'     // 泰文、老挝文
+        | '\u{1000}'..='\u{109F}'   // 缅甸文
+        | '\u{1780}'..='\u{17FF}')  // 高棉文
+}
+
+fn is_cjk_char(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'     // 平假名、片假名
+        | '\u{3400}'..='\u{4DBF}'   // CJK 扩展 A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK 统一汉字
+        | '\u{AC00}'..='\u{D7AF}'   // 韩文音节
+        | '\u{F900}'..='\u{FAFF}')  // CJK 兼容汉字
+}
+
+const DEFAULT_SUMMARY_MAX_TOKENS: u32 = 500;
+const DEFAULT_SUMMARY_TEMPERATURE: f32 = 0.7;
+const MAX_SUMMARY_TEMPERATURE: f32 = 2.0;
+
+/// 解析并校验后的总结参数
+#[derive(Clone)]
+struct SummaryOptions {
+    api_key: Option<String>,
+    provider: ApiProvider,
+    /// 覆盖服务默认模型的模型名称
+    model: Option<String>,
+    /// 主语言的系统提示词
+    system_prompt: String,
+    /// 使用自定义提示词时为空
+    style: Option<SummaryStyle>,
+    primary_language: Option<String>,
+    /// 主语言之外还需要生成总结的语言及其系统提示词
+    extra_languages: Vec<(String, String)>,
+    offline: bool,
+    proxy: Option<String>,
+    max_tokens: u32,
+    temperature: f32,
+}
+
+impl SummaryOptions {
+    fn from_pipeline_options(options: &PipelineOptions, proxy: Option<String>) -> Result<Self, PipelineError> {
+        let languages = options.summary_language.as_ref().map(StringOrList::to_vec).unwrap_or_default();
+        let summary_prompt = options.summary_prompt.as_deref().filter(|prompt| !prompt.trim().is_empty());
+        let style = options.summary_style.unwrap_or_default();
+        Ok(SummaryOptions {
+            api_key: options.api_key.clone(),
+            provider: ApiProvider::from_options(options)?,
+            model: options.summary_model.as_ref().map(|model| model.trim().to_string()),
+            system_prompt: build_summary_system_prompt(summary_prompt, style, languages.first().map(String::as_str)),
+            style: summary_prompt.is_none().then_some(style),
+            primary_language: languages.first().cloned(),
+            extra_languages: languages.iter().skip(1)
+                .map(|language| (language.clone(), build_language_summary_prompt(summary_prompt, style, language)))
+                .collect(),
+            offline: options.offline,
+            proxy,
+            max_tokens: options.max_tokens.unwrap_or(style.max_tokens()),
+            temperature: options.temperature.unwrap_or(DEFAULT_SUMMARY_TEMPERATURE),
+        })
+    }
+    
+    fn model(&self) -> &str {
+        self.model.as_deref().unwrap_or(self.provider.default_model())
+    }
+    
+    fn validate(&self) -> Result<(), PipelineError> {
+        if self.model.as_deref().is_some_and(str::is_empty) {
+            return Err(PipelineError::InvalidInput("总结模型名称不能为空".to_string()));
+        }
+        if self.max_tokens == 0 {
+            return Err(PipelineError::InvalidInput("max_tokens 必须大于 0".to_string()));
+        }
+        if !(0.0..=MAX_SUMMARY_TEMPERATURE).contains(&self.temperature) {
+            return Err(PipelineError::InvalidInput(format!("temperature 必须在 0.0 到 {} 之间，当前为 {}", MAX_SUMMARY_TEMPERATURE, self.temperature)));
+        }
+        Ok(())
+    }
+}
+
+/// 先生成主语言的总结，请求了多种语言时再逐个语言调用一次模型；
+/// 其他语言失败时只记录警告，返回已成功的部分
+#[tracing::instrument(level = "debug", name = "summarize", skip_all, fields(provider = options.provider.name(), model = options.model()))]
+async fn summarize_transcript_content(transcript: &str, options: &SummaryOptions) -> Result<SummaryResult, PipelineError> {
+    let mut result = summarize_transcript_with_chunk_size(transcript, options, DEFAULT_SUMMARY_CHUNK_CHARS).await?;
+    
+    let Some(primary_language) = &options.primary_language else {
+        return Ok(result);
+    };
+    // 主语言已回退到简单总结时接口不可用，不再请求其他语言
+    if options.extra_languages.is_empty() || result.provider == SIMPLE_SUMMARY_PROVIDER {
+        return Ok(result);
+    }
+    
+    let client = build_http_client(options.proxy.as_deref())?;
+    let mut summaries = HashMap::from([(primary_language.clone(), result.text.clone())]);
+    for (language, system_prompt) in &options.extra_languages {
+        let language_options = SummaryOptions { system_prompt: system_prompt.clone(), ..options.clone() };
+        match request_summary(&client, &language_options, transcript, DEFAULT_SUMMARY_CHUNK_CHARS).await {
+            Ok(summary) => {
+                summaries.insert(language.clone(), summary);
+            }
+            Err(CompletionError::Network(e) | CompletionError::RateLimited(e) | CompletionError::Auth(e) | CompletionError::Api(e)) => {
+                result.warnings.push(format!("生成{}总结失败: {}", language, e));
+            }
+        }
+    }
+    result.summaries = Some(summaries);
+    Ok(result)
+}
+
+/// yt-dlp 元数据中的一个章节，时间单位为秒
+#[derive(Deserialize)]
+struct VideoChapter {
+    title: String,
+    start_time: f64,
+    end_time: f64,
+}
+
+/// 从保存的元数据中读取章节，文件缺失或没有章节时返回空列表
+fn read_chapters(metadata_file: &str) -> Vec<VideoChapter> {
+    fs::read(metadata_file).ok()
+        .and_then(|body| serde_json::from_slice::<serde_json::Value>(&body).ok())
+        .and_then(|metadata| serde_json::from_value(metadata["chapters"].clone()).ok())
+        .unwrap_or_default()
+}
+
+/// 按章节的时间范围切出带时间戳的分段，逐章调用模型总结。
+/// 缺少分段或元数据、视频没有章节时返回 None，只保留整体总结
+async fn summarize_chapters(record: &VideoRecord, options: &SummaryOptions) -> Result<Option<Vec<ChapterSummary>>, PipelineError> {
+    let (Some(segments), Some(metadata_file)) = (&record.segments, &record.metadata_file) else {
+        return Ok(None);
+    };
+    let chapters = read_chapters(metadata_file);
+    if chapters.is_empty() {
+        return Ok(None);
+    }
+    
+    // 只下载了一段时分段时间从片段开头算起，章节时间是相对整个视频的
+    let offset = record.time_range.map_or(0.0, |range| range.start as f64);
+    let client = build_http_client(options.proxy.as_deref())?;
+    let mut summaries = Vec::new();
+    for chapter in chapters {
+        let (start, end) = (chapter.start_time - offset, chapter.end_time - offset);
+        // 跨章节的分段按中点归入一章
+        let text = segments.iter()
+            .filter(|segment| (start..end).contains(&((segment.start + segment.end) / 2.0)))
+            .map(|segment| segment.text.trim())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        if text.trim().is_empty() {
+            continue;
+        }
+        
+        let summary = if text.chars().count() > DEFAULT_SUMMARY_CHUNK_CHARS {
+            request_summary(&client, options, &text, DEFAULT_SUMMARY_CHUNK_CHARS).await
+        } else {
+            let user_prompt = format!("以下是视频中「{}」一章的转录内容，请简要总结这一章的主要观点：\n\n{}", chapter.title.trim(), text);
+            request_completion(&client, options, &options.system_prompt, &user_prompt).await
+        };
+        let summary = match summary {
+            Ok(summary) => summary,
+            Err(CompletionError::Auth(e)) => return Err(PipelineError::ApiAuthFailed(e)),
+            Err(CompletionError::Network(e) | CompletionError::RateLimited(e) | CompletionError::Api(e)) => {
+                return Err(PipelineError::SummarizationFailed(format!("章节「{}」: {}", chapter.title.trim(), e)));
+            }
+        };
+        summaries.push(ChapterSummary {
+            title: chapter.title.trim().to_string(),
+            start: chapter.start_time,
+            end: chapter.end_time,
+            summary,
+        });
+    }
+    Ok(Some(summaries).filter(|summaries| !summaries.is_empty()))
+}
+
+async fn summarize_transcript_with_chunk_size(transcript: &str, options: &SummaryOptions, chunk_size: usize) -> Result<SummaryResult, PipelineError> {
+    // 离线模式直接使用简单总结
+    if options.offline {
+        return Ok(SummaryResult::new(generate_simple_summary(transcript, false), transcript, SIMPLE_SUMMARY_PROVIDER));
+    }
+    // 如果没有提供API密钥，使用本地LLM或返回简单总结
+    if options.api_key.is_none() && options.provider.requires_api_key() {
+        return Ok(SummaryResult::new(generate_simple_summary(transcript, true), transcript, SIMPLE_SUMMARY_PROVIDER));
+    }
+    
+    let client = build_http_client(options.proxy.as_deref())?;
+    
+    match request_summary(&client, options, transcript, chunk_size).await {
+        Ok(summary) => {
+            let mut result = SummaryResult::new(summary, transcript, options.provider.name());
+            result.model = Some(options.model().to_string());
+            result.style = options.style;
+            // 关键词只是附加信息，提取失败时保留按词频提取的结果
+            match request_keywords(&client, options, &result.text).await {
+                Ok(keywords) => result.keywords = keywords,
+                Err(CompletionError::Network(e) | CompletionError::RateLimited(e) | CompletionError::Auth(e) | CompletionError::Api(e)) => {
+                    tracing::warn!("提取关键词失败，使用词频统计结果: {}", e);
+                }
+            }
+            Ok(result)
+        }
+        Err(CompletionError::Auth(e)) => Err(PipelineError::ApiAuthFailed(e)),
+        Err(CompletionError::Api(e)) => Err(PipelineError::SummarizationFailed(e)),
+        Err(CompletionError::Network(e) | CompletionError::RateLimited(e)) => {
+            // 接口暂时不可用时回退到简单总结，summary_provider 会记为 simple
+            tracing::warn!("API暂时不可用，使用简单总结: {}", e);
+            Ok(SummaryResult::new(generate_simple_summary(transcript, true), transcript, SIMPLE_SUMMARY_PROVIDER))
+        }
+    }
+}
+
+fn build_http_client(proxy: Option<&str>) -> Result<reqwest::Client, PipelineError> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| PipelineError::InvalidInput(format!("无效的代理地址 {}: {}", proxy, e)))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build()
+        .map_err(|e| PipelineError::Internal(format!("创建 HTTP 客户端失败: {}", e)))
+}
+
+/// 用 options 中的系统提示词总结转录文本，超过 chunk_size 时分段总结
+async fn request_summary(client: &reqwest::Client, options: &SummaryOptions, transcript: &str, chunk_size: usize) -> Result<String, CompletionError> {
+    let chunks = split_transcript_chunks(transcript, chunk_size, SUMMARY_CHUNK_OVERLAP_CHARS);
+    if chunks.len() == 1 {
+        let user_prompt = format!("请总结以下视频转录内容，提取主要观点和重要信息：\n\n{}", transcript);
+        request_completion(client, options, &options.system_prompt, &user_prompt).await
+    } else {
+        summarize_chunks(client, options, &chunks).await
+    }
+}
+
+/// 先逐段总结，再对各段总结做一次汇总
+async fn summarize_chunks(client: &reqwest::Client, options: &SummaryOptions, chunks: &[String]) -> Result<String, CompletionError> {
+    let total = chunks.len();
+    let mut partial_summaries = Vec::with_capacity(total);
+    
+    for (index, chunk) in chunks.iter().enumerate() {
+        let user_prompt = format!(
+            "以下是一段视频转录内容的第{}/{}部分，请总结这一部分的主要观点和重要信息：\n\n{}",
+            index + 1, total, chunk
+        );
+        partial_summaries.push(request_completion(client, options, &options.system_prompt, &user_prompt).await?);
+    }
+    
+    let combined = partial_summaries.iter()
+        .enumerate()
+        .map(|(index, summary)| format!("【第{}部分】\n{}", index + 1, summary))
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    let user_prompt = format!(
+        "以下是同一个视频各部分内容的分段总结，请将它们整合为一份完整、连贯的总结，提取主要观点和重要信息：\n\n{}",
+        combined
+    );
+    request_completion(client, options, &options.system_prompt, &user_prompt).await
+}
+
+/// 按字符数把转录文本切成相互重叠的分段，尽量在句末或空白处断开
+fn split_transcript_chunks(transcript: &str, chunk_size: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<char> = transcript.chars().collect();
+    let chunk_size = chunk_size.max(1);
+    if chars.len() <= chunk_size {
+        return vec![transcript.to_string()];
+    }
+    
+    // 重叠部分不超过分段长度的一半，保证每一段都在向前推进
+    let overlap = overlap.min(chunk_size / 2);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    
+    loop {
+        let mut end = (start + chunk_size).min(chars.len());
+        if end < chars.len() {
+            let search_from = start + chunk_size * 4 / 5;
+            if let Some(boundary) = (search_from..end).rev().find(|&i| is_chunk_boundary(chars[i])) {
+                end = boundary + 1;
+            }
+        }
+        
+        chunks.push(chars[start..end].iter().collect());
+        
+        if end == chars.len() {
+            break;
+        }
+        start = end - overlap;
+    }
+    
+    chunks
+}
+
+fn is_chunk_boundary(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '.' | '!' | '?' | '。' | '！' | '？')
+}
+
+/// 简单总结中选取的句子数
+const SIMPLE_SUMMARY_SENTENCES: usize = 3;
+
+/// 不调用模型的本地总结：按词频选出最能代表全文的几句话。离线模式下不显示配置 API 的提示
+fn generate_simple_summary(transcript: &str, show_api_hint: bool) -> String {
+    let total_words = count_words(transcript);
+    
+    if total_words == 0 {
+        return "转录内容为空，无法生成总结。".to_string();
+    }
+    
+    let summary_sentences = join_sentences(&select_key_sentences(transcript, SIMPLE_SUMMARY_SENTENCES), is_mostly_cjk(transcript));
+    let hint = if show_api_hint { "\n\n💡 提示：配置OpenAI API密钥可获得更精准的AI总结" } else { "" };
+    
+    format!(
+        "📊 内容统计：共约{}词\n\n📝 内容概要：\n{}{}", 
+        total_words, 
+        if summary_sentences.is_empty() { "转录内容较短，建议查看完整转录文本" } else { &summary_sentences },
+        hint
+    )
+}
+
+/// 中日韩的句末标点后面不加空格，可以直接断句
+const CJK_SENTENCE_ENDINGS: [char; 3] = ['。', '！', '？'];
+/// 英文等的句末标点只有后面是空白或文本结尾时才断句，避免切开 3.14 这样的数字
+const SENTENCE_ENDINGS: [char; 3] = ['.', '!', '?'];
+
+/// 按句末标点或换行切分句子，保留句末标点
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\n' {
+            current.push(c);
+        }
+        let ends_sentence = c == '\n'
+            || CJK_SENTENCE_ENDINGS.contains(&c)
+            || (SENTENCE_ENDINGS.contains(&c) && chars.peek().is_none_or(|next| next.is_whitespace()));
+        if ends_sentence {
+            let sentence = current.trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+            current.clear();
+        }
+    }
+    let sentence = current.trim();
+    if !sentence.is_empty() {
+        sentences.push(sentence.to_string());
+    }
+    sentences
+}
+
+/// 中日韩文字多于其他文字时按中日韩文本处理
+fn is_mostly_cjk(text: &str) -> bool {
+    let cjk_chars = text.chars().filter(|c| is_cjk_char(*c)).count();
+    let other_chars = text.chars().filter(|c| c.is_alphabetic() && !is_cjk_char(*c)).count();
+    cjk_chars > other_chars
+}
+
+/// 按文本的语言拼接句子：中日韩文本直接相连，缺少句末标点的补上"。"；其他语言以空格分隔，缺少时补上"."
+fn join_sentences(sentences: &[String], cjk: bool) -> String {
+    let (ending, separator) = if cjk { ('。', "") } else { ('.', " ") };
+    sentences.iter()
+        .map(|sentence| {
+            let ends_with_punctuation = sentence.ends_with(|c: char| CJK_SENTENCE_ENDINGS.contains(&c) || SENTENCE_ENDINGS.contains(&c));
+            if ends_with_punctuation {
+                sentence.clone()
+            } else {
+                format!("{}{}", sentence, ending)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+/// 整理转录文本时每段包含的句子数
+const PARAGRAPH_SENTENCES: usize = 5;
+
+/// 把 whisper 逐行输出的文本整理成段落：先合并各行并规整空白，再每隔几句分段
+fn format_transcript(raw: &str) -> String {
+    let lines: Vec<String> = raw.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    let sentences = split_sentences(&join_with_spacing(&lines));
+    sentences.chunks(PARAGRAPH_SENTENCES)
+        .map(join_with_spacing)
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// 拼接文本片段：两侧都是中文、日文等不用空格分词的文字时直接相连，否则以空格分隔
+fn join_with_spacing(parts: &[String]) -> String {
+    let mut joined = String::new();
+    for part in parts {
+        let needs_space = match (joined.chars().last(), part.chars().next()) {
+            (Some(prev), Some(next)) => !(joins_without_space(prev) && joins_without_space(next)),
+            _ => false,
+        };
+        if needs_space {
+            joined.push(' ');
+        }
+        joined.push_str(part);
+    }
+    joined
+}
+
+/// 韩文虽然属于中日韩文字，但词之间用空格分隔
+fn joins_without_space(c: char) -> bool {
+    let is_hangul = matches!(c, '\u{AC00}'..='\u{D7AF}');
+    (is_unspaced_script_char(c) && !is_hangul)
+        || matches!(c,
+            '\u{3000}'..='\u{303F}'   // 中日韩标点
+            | '\u{FF00}'..='\u{FFEF}') // 全角字符
+}
+
+/// 选出得分最高的几句并按原文顺序返回。句子得分为其中各词在全文中出现次数之和除以词数的平方根，
+/// 包含高频词的句子得分高，同时不会一味偏向长句
+fn select_key_sentences(text: &str, limit: usize) -> Vec<String> {
+    let sentences = split_sentences(text);
+    let mut frequencies: HashMap<String, usize> = HashMap::new();
+    for term in keyword_terms(text) {
+        *frequencies.entry(term).or_insert(0) += 1;
+    }
     
-    let request = ChatCompletionRequest {
-        model: provider.default_model().to_string(),
-        messages,
-        max_tokens: 500,
-        temperature: 0.7,
-    };
+    let mut scored: Vec<(f64, usize)> = sentences.iter()
+        .enumerate()
+        .map(|(index, sentence)| {
+            let terms = keyword_terms(sentence);
+            if terms.is_empty() {
+                return (0.0, index);
+            }
+            let total: usize = terms.iter().map(|term| frequencies.get(term).copied().unwrap_or(1)).sum();
+            (total as f64 / (terms.len() as f64).sqrt(), index)
+        })
+        .collect();
+    // 得分相同时保留靠前的句子
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
     
-    match client
-        .post(provider.base_url())
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                match response.json::<ChatCompletionResponse>().await {
-                    Ok(chat_response) => {
-                        if let Some(choice) = chat_response.choices.first() {
-                            Ok(choice.message.content.clone())
-                        } else {
-                            Err("API返回了空的总结结果".to_string())
-                        }
-                    }
-                    Err(e) => Err(format!("解析API响应失败: {}", e)),
+    let mut selected: Vec<usize> = scored.into_iter().take(limit).map(|(_, index)| index).collect();
+    selected.sort_unstable();
+    selected.into_iter().map(|index| sentences[index].clone()).collect()
+}
+
+/// yt-dlp 下载过程中的临时文件后缀，不能当作完整的音频文件
+const YT_DLP_TEMP_EXTENSIONS: [&str; 2] = ["part", "ytdl"];
+
+/// 是否为 yt-dlp 未完成的文件：下载中的 `.part`/`.ytdl`、ffmpeg 转换中的 `<标题>.temp.<扩展名>`，或者空文件
+fn is_incomplete_download(path: &Path) -> bool {
+    let empty = fs::metadata(path).map(|metadata| metadata.len() == 0).unwrap_or(true);
+    is_download_temp_file(path) || empty
+}
+
+/// 是否为 yt-dlp 下载或转换途中的临时文件
+fn is_download_temp_file(path: &Path) -> bool {
+    let extension = path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let converting = path.file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().to_lowercase().ends_with(".temp"));
+    YT_DLP_TEMP_EXTENSIONS.contains(&extension.as_str()) || converting
+}
+
+/// 删除取消下载后留下的临时文件和合并前的单独格式文件，目录中的其他文件保持不变
+fn remove_download_leftovers(dir: &Path) {
+    if let Ok(entries) = fs::read_dir(dir) {
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_file() && is_download_temp_file(&path) {
+                if let Err(e) = fs::remove_file(&path) {
+                    tracing::warn!("删除未完成的下载文件失败 {}: {}", path.display(), e);
                 }
-            } else {
-                Err(format!("API请求失败，状态码: {}", response.status()))
             }
         }
-        Err(e) => {
-            // 网络错误时回退到简单总结
-            eprintln!("API调用失败，使用简单总结: {}", e);
-            Ok(generate_simple_summary(&transcript))
+    }
+    remove_format_fragments(dir);
+}
+
+/// find_audio_file 找到的音频文件
+struct FoundAudioFile {
+    path: String,
+    /// 目录中其他完整的候选音频文件，非空说明选择存在歧义
+    other_candidates: Vec<String>,
+}
+
+impl FoundAudioFile {
+    fn ambiguity_warning(&self) -> Option<String> {
+        if self.other_candidates.is_empty() {
+            return None;
         }
+        Some(format!(
+            "⚠️ 目录中有多个音频文件，已选择最新修改的 {}，忽略: {}",
+            self.path,
+            self.other_candidates.join(", ")
+        ))
     }
 }
 
-fn generate_simple_summary(transcript: &str) -> String {
-    let words: Vec<&str> = transcript.split_whitespace().collect();
-    let total_words = words.len();
+/// 在目录中查找音频文件，跳过 yt-dlp 的临时文件；有多个候选时选最近修改的那个
+fn find_audio_file(dir: &Path, format: Option<&str>) -> Option<FoundAudioFile> {
+    let audio_extensions = match format {
+        Some(format) => vec![audio_format_extension(format)],
+        None => vec!["wav", "mp3", "m4a", "aac", "flac", "ogg", "opus"],
+    };
     
-    if total_words == 0 {
-        return "转录内容为空，无法生成总结。".to_string();
-    }
+    let mut candidates: Vec<(std::time::SystemTime, PathBuf)> = fs::read_dir(dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && !is_incomplete_download(path))
+        .filter(|path| {
+            let extension = path.extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            audio_extensions.contains(&extension.as_str())
+        })
+        .map(|path| {
+            let modified = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+            (modified, path)
+        })
+        .collect();
     
-    // 简单的总结：取前几句话
-    let sentences: Vec<&str> = transcript.split('.').collect();
-    let summary_sentences = sentences.iter()
-        .take(3)
-        .filter(|s| !s.trim().is_empty())
-        .map(|s| s.trim())
-        .collect::<Vec<&str>>()
-        .join("。");
+    // 最新的排在最前面
+    candidates.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    let mut paths = candidates.into_iter().map(|(_, path)| path.to_string_lossy().to_string());
+    let path = paths.next()?;
     
-    format!(
-        "📊 内容统计：共约{}词\n\n📝 内容概要：\n{}\n\n💡 提示：配置OpenAI API密钥可获得更精准的AI总结", 
-        total_words, 
-        if summary_sentences.is_empty() { "转录内容较短，建议查看完整转录文本" } else { &summary_sentences }
-    )
+    Some(FoundAudioFile {
+        path,
+        other_candidates: paths.collect(),
+    })
 }
 
-fn find_audio_file(dir: &Path) -> Option<String> {
-    if !dir.exists() {
-        return None;
-    }
-    
-    let audio_extensions = ["wav", "mp3", "m4a", "aac", "flac", "ogg"];
-    
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_file() {
-                if let Some(extension) = path.extension() {
-                    let ext_str = extension.to_string_lossy().to_lowercase();
-                    if audio_extensions.contains(&ext_str.as_str()) {
-                        return Some(path.to_string_lossy().to_string());
-                    }
-                }
+const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "mkv", "webm", "mov"];
+
+/// 在目录中查找 keep_video 保留的视频文件
+fn find_video_file(dir: &Path) -> Option<String> {
+    fs::read_dir(dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && !is_incomplete_download(path))
+        .find(|path| path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| VIDEO_EXTENSIONS.contains(&extension.as_str())))
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// 删除 yt-dlp 合并格式前下载的单独画面和音轨，文件名形如 `<标题>.f<格式编号>.<扩展名>`
+fn remove_format_fragments(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let is_fragment = path.file_stem()
+            .and_then(|stem| Path::new(stem).extension().map(|format| format.to_string_lossy().to_string()))
+            .is_some_and(|format| format.len() > 1 && format.starts_with('f') && format[1..].chars().all(|c| c.is_ascii_digit()));
+        if is_fragment && path.is_file() {
+            if let Err(e) = fs::remove_file(&path) {
+                tracing::warn!("删除临时格式文件失败 {}: {}", path.display(), e);
             }
         }
     }
-    None
 }
 
-fn find_transcript_file(audio_file_path: &str) -> Option<String> {
+/// 在目录中查找 yt-dlp 保存的视频缩略图
+fn find_thumbnail_file(dir: &Path) -> Option<String> {
+    let thumbnail_extensions = ["jpg", "png", "webp"];
+    
+    fs::read_dir(dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .find(|path| path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| thumbnail_extensions.contains(&extension.as_str())))
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// 查找 whisper 为音频生成的指定格式（txt/srt/vtt 等）输出文件。
+/// 优先匹配与音频同名的文件；只有当音频所在目录中恰好有一个该格式的文件时才退而使用它，
+/// 避免多个音频共用目录时拿到别的音频的转录结果。
+fn find_transcript_file(audio_file_path: &str, parent_dir: &Path, format: &str) -> Option<String> {
     let audio_path = Path::new(audio_file_path);
-    let parent_dir = audio_path.parent()?;
     let stem = audio_path.file_stem()?.to_string_lossy();
     
-    // Whisper 通常会生成与音频文件同名但扩展名为 .txt 的文件
-    let transcript_path = parent_dir.join(format!("{}.txt", stem));
+    // Whisper 通常会生成与音频文件同名、扩展名为对应格式的文件
+    let transcript_path = parent_dir.join(format!("{}.{}", stem, format));
+    if transcript_path.is_file() {
+        return Some(transcript_path.to_string_lossy().to_string());
+    }
     
-    if transcript_path.exists() {
-        Some(transcript_path.to_string_lossy().to_string())
-    } else {
-        // 也尝试查找目录中的其他 .txt 文件
-        if let Ok(entries) = std::fs::read_dir(parent_dir) {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if let Some(extension) = path.extension() {
-                    if extension == "txt" {
-                        return Some(path.to_string_lossy().to_string());
-                    }
-                }
-            }
+    let mut candidates = fs::read_dir(parent_dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == format));
+    
+    match (candidates.next(), candidates.next()) {
+        (Some(only), None) => Some(only.to_string_lossy().to_string()),
+        _ => None,
+    }
+}
+
+/// 按天轮转后的日志文件名形如 `video-transcriber.2024-01-01.log`
+const LOG_FILE_PREFIX: &str = "video-transcriber";
+const LOG_FILE_SUFFIX: &str = "log";
+/// vault 下存放日志的目录，隐藏目录不会被当作孤立目录
+const LOG_DIR_NAME: &str = ".logs";
+/// 最多保留的日志文件数（天）
+const LOG_MAX_FILES: usize = 7;
+/// 日志级别的环境变量，语法同 RUST_LOG，如 debug 或 video_transcriber_lib=trace；debug 会记录外部工具的调用参数、退出码和各步骤耗时
+const LOG_LEVEL_ENV: &str = "VIDEO_TRANSCRIBER_LOG";
+/// get_logs 默认返回的行数
+const DEFAULT_LOG_LINES: usize = 500;
+
+type LogFileLayer = tracing_subscriber::fmt::Layer<tracing_subscriber::Registry, tracing_subscriber::fmt::format::DefaultFields, tracing_subscriber::fmt::format::Format, tracing_appender::rolling::RollingFileAppender>;
+
+/// 当前写入日志文件的 vault 及切换写入目录的句柄
+struct LogFileTarget {
+    vault_path: PathBuf,
+    handle: tracing_subscriber::reload::Handle<LogFileLayer, tracing_subscriber::Registry>,
+}
+
+/// init_logging 之前或日志文件无法创建时为 None，此时只输出到 stderr
+static LOG_FILE_TARGET: Mutex<Option<LogFileTarget>> = Mutex::new(None);
+
+fn log_file_appender(vault_path: &Path) -> Result<tracing_appender::rolling::RollingFileAppender, String> {
+    let log_dir = vault_path.join(LOG_DIR_NAME);
+    // 目录不存在时 tracing_appender 清理旧日志会先报一次读取失败
+    fs::create_dir_all(&log_dir).map_err(|e| e.to_string())?;
+    tracing_appender::rolling::Builder::new()
+        .rotation(tracing_appender::rolling::Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(LOG_MAX_FILES)
+        .build(log_dir)
+        .map_err(|e| e.to_string())
+}
+
+/// 日志同时输出到 stderr 和 vault 下的日志文件。启动时还不知道前端使用的 base_path，先写到默认 vault，
+/// 运行流水线时由 use_log_vault 切换到实际使用的 vault
+fn init_logging() {
+    use tracing_subscriber::prelude::*;
+    use tracing_subscriber::fmt::format::FmtSpan;
+    
+    let vault_path = resolve_vault_path(None);
+    let (file_layer, handle) = match log_file_appender(&vault_path) {
+        Ok(appender) => {
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_writer(appender);
+            let (layer, handle) = tracing_subscriber::reload::Layer::new(layer);
+            (Some(layer), Some(handle))
         }
-        None
+        Err(e) => {
+            eprintln!("创建日志文件失败，只输出到 stderr: {}", e);
+            (None, None)
+        }
+    };
+    let filter = tracing_subscriber::EnvFilter::builder()
+        .with_default_directive(tracing::level_filters::LevelFilter::INFO.into())
+        .with_env_var(LOG_LEVEL_ENV)
+        .from_env_lossy();
+    let initialized = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_span_events(FmtSpan::CLOSE))
+        .with(filter)
+        .try_init();
+    match initialized {
+        Ok(()) => *LOG_FILE_TARGET.lock().unwrap() = handle.map(|handle| LogFileTarget { vault_path, handle }),
+        Err(e) => eprintln!("初始化日志失败: {}", e),
+    }
+}
+
+/// 把日志文件切换到 vault_path 下，已在写入该 vault 时不做任何事
+fn use_log_vault(vault_path: &Path) {
+    let mut target = LOG_FILE_TARGET.lock().unwrap();
+    let Some(target) = target.as_mut().filter(|target| target.vault_path != vault_path) else {
+        return;
+    };
+    let switched = log_file_appender(vault_path)
+        .and_then(|appender| target.handle.modify(|layer| *layer.writer_mut() = appender).map_err(|e| e.to_string()));
+    match switched {
+        Ok(()) => target.vault_path = vault_path.to_path_buf(),
+        Err(e) => tracing::warn!("切换日志目录失败，继续写入 {}: {}", target.vault_path.display(), e),
+    }
+}
+
+/// 返回 vault 日志最后若干行（可能跨越多天的日志文件），供用户在反馈问题时附上
+#[tauri::command]
+fn get_logs(base_path: Option<String>, lines: Option<usize>) -> Result<String, PipelineError> {
+    let log_dir = resolve_vault_path(base_path).join(LOG_DIR_NAME);
+    let mut log_files: Vec<PathBuf> = match fs::read_dir(&log_dir) {
+        Ok(entries) => entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_SUFFIX)
+            }))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(PipelineError::Internal(format!("读取日志目录失败: {}", e))),
+    };
+    // 文件名中的日期使日志按名称排序即按时间排序，从最新的文件往前读到足够的行数
+    log_files.sort();
+    let wanted = lines.unwrap_or(DEFAULT_LOG_LINES);
+    let mut tail: Vec<String> = Vec::new();
+    for path in log_files.iter().rev() {
+        if tail.len() >= wanted {
+            break;
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| PipelineError::Internal(format!("读取日志失败: {}", e)))?;
+        let mut file_lines: Vec<String> = content.lines().map(str::to_string).collect();
+        file_lines.append(&mut tail);
+        tail = file_lines;
     }
+    let start = tail.len().saturating_sub(wanted);
+    Ok(tail[start..].join("\n"))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -548,7 +6722,917 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .invoke_handler(tauri::generate_handler![greet, select_download_path, process_video_pipeline])
+        .setup(|_app| {
+            init_logging();
+            Ok(())
+        })
+        .manage(PipelineRegistry::default())
+        .manage(TranscriptionQueue::new(DEFAULT_TRANSCRIBE_CONCURRENCY))
+        .invoke_handler(tauri::generate_handler![greet, check_environment, select_download_path, process_video_pipeline, pipeline_video_id, preview_pipeline, process_local_file, transcribe_recording, process_playlist, process_batch, cancel_pipeline, queue_status, set_transcription_concurrency, list_videos, get_video, get_transcript, search_transcripts, vault_stats, relocate_vault, delete_video, update_video_title, set_video_notes, set_video_tags, merge_videos, open_video_folder, export_video_markdown, verify_vault, repair_vault, export_vault, import_vault, migrate_vault_layout, find_orphans, prune_orphans, retry_video, resume_all, retranscribe_video, redownload_video, resummarize_video, estimate_summary_cost, validate_api_key, get_vault_config, set_vault_config, get_logs])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在本机启动一个 OpenAI 兼容的 chat/completions 接口，第 n 个请求回复 `回复n`，返回接口地址和收到的请求体
+    fn serve_chat_completions() -> (String, Arc<Mutex<Vec<String>>>) {
+        use std::io::{BufRead, Read, Write};
+        
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = std::io::BufReader::new(stream.unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let index = {
+                    let mut received = received.lock().unwrap();
+                    received.push(String::from_utf8(body).unwrap());
+                    received.len() - 1
+                };
+                let reply = serde_json::json!({ "choices": [{ "message": { "role": "assistant", "content": format!("回复{}", index) } }] }).to_string();
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", reply.len(), reply);
+                reader.into_inner().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (base_url, requests)
+    }
+
+    fn custom_summary_options(base_url: &str) -> SummaryOptions {
+        let options = PipelineOptions {
+            api_provider: Some("custom".to_string()),
+            custom_base_url: Some(base_url.to_string()),
+            custom_model: Some("test-model".to_string()),
+            ..Default::default()
+        };
+        SummaryOptions::from_pipeline_options(&options, None).unwrap()
+    }
+
+    fn test_record(id: &str, url: &str) -> VideoRecord {
+        VideoRecord {
+            id: id.to_string(),
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn invalid_whisper_model_is_rejected_before_transcribing() {
+        let options = PipelineOptions { whisper_model: Some("huge".to_string()), ..Default::default() };
+        let error = TranscribeOptions::from_pipeline_options(&options).validate().unwrap_err();
+        assert!(matches!(error, PipelineError::InvalidInput(message) if message.contains("huge")));
+        
+        let options = PipelineOptions { whisper_model: Some("small".to_string()), ..Default::default() };
+        assert!(TranscribeOptions::from_pipeline_options(&options).validate().is_ok());
+    }
+
+    #[test]
+    fn subtitle_lookup_does_not_replace_the_transcript_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let audio = dir.path().join("video.wav");
+        for name in ["video.wav", "video.txt", "video.srt"] {
+            fs::write(dir.path().join(name), "data").unwrap();
+        }
+        let audio = audio.to_string_lossy();
+        let transcript_file = find_transcript_file(&audio, dir.path(), "txt").unwrap();
+        let srt_file = find_transcript_file(&audio, dir.path(), "srt").unwrap();
+        assert!(transcript_file.ends_with("video.txt"));
+        assert!(srt_file.ends_with("video.srt"));
+        assert_eq!(find_transcript_file(&audio, dir.path(), "vtt"), None);
+    }
+
+    #[test]
+    fn parses_anthropic_messages_response() {
+        let body = r#"{
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-5-sonnet-latest",
+            "content": [
+                {"type": "text", "text": "总结：\n"},
+                {"type": "tool_use", "id": "toolu_01", "name": "noop", "input": {}},
+                {"type": "text", "text": "视频介绍了 Rust。"}
+            ],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 12, "output_tokens": 8}
+        }"#;
+        assert_eq!(parse_anthropic_response(body).unwrap(), "总结：\n视频介绍了 Rust。");
+        assert!(parse_anthropic_response(r#"{"content": []}"#).is_err());
+        assert!(parse_anthropic_response("not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn long_transcript_is_summarized_per_chunk_then_combined() {
+        let (base_url, requests) = serve_chat_completions();
+        let options = custom_summary_options(&base_url);
+        let transcript = "第一句话讲的是背景。第二句话讲的是方法。第三句话讲的是结果。".repeat(4);
+        
+        let chunks = split_transcript_chunks(&transcript, 40, SUMMARY_CHUNK_OVERLAP_CHARS);
+        assert!(chunks.len() > 1);
+        let result = summarize_transcript_with_chunk_size(&transcript, &options, 40).await.unwrap();
+        
+        // 每段一次、汇总一次，最后一次是提取关键词
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), chunks.len() + 2);
+        assert!(requests[0].contains(&format!("第1/{}部分", chunks.len())));
+        assert!(requests[chunks.len()].contains("分段总结"));
+        assert_eq!(result.text, format!("回复{}", chunks.len()));
+        assert_eq!(result.provider, "custom");
+    }
+
+    #[tokio::test]
+    async fn transcript_within_one_chunk_is_summarized_in_a_single_request() {
+        let (base_url, requests) = serve_chat_completions();
+        let options = custom_summary_options(&base_url);
+        let transcript = "第一句话讲的是背景。第二句话讲的是结果。";
+        
+        assert_eq!(split_transcript_chunks(transcript, 1000, SUMMARY_CHUNK_OVERLAP_CHARS), vec![transcript.to_string()]);
+        let result = summarize_transcript_with_chunk_size(transcript, &options, 1000).await.unwrap();
+        
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("请总结以下视频转录内容") && requests[0].contains(transcript));
+        assert_eq!(result.text, "回复0");
+    }
+
+    #[test]
+    fn delete_video_removes_record_and_directory() {
+        let base = tempfile::tempdir().unwrap();
+        let vault_path = resolve_vault_path(Some(base.path().to_string_lossy().to_string()));
+        let video_dir = vault_path.join("abc");
+        fs::create_dir_all(&video_dir).unwrap();
+        fs::write(video_dir.join("audio.wav"), "audio").unwrap();
+        update_vault(&vault_path, |vault| {
+            vault.videos.insert("abc".to_string(), test_record("abc", "https://example.com/a"));
+            vault.videos.insert("def".to_string(), test_record("def", "https://example.com/d"));
+        }).unwrap();
+        
+        remove_video(&vault_path, "abc").unwrap();
+        
+        assert!(!video_dir.exists());
+        let vault = load_vault(&vault_path).unwrap();
+        assert!(!vault.videos.contains_key("abc"));
+        assert!(vault.videos.contains_key("def"));
+    }
+
+    #[test]
+    fn youtube_url_shapes_map_to_one_video_id() {
+        let vault = Vault { version: VAULT_VERSION, videos: HashMap::new() };
+        let ids: Vec<String> = [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42s",
+            "https://youtu.be/dQw4w9WgXcQ?si=share",
+            "https://m.youtube.com/shorts/dQw4w9WgXcQ",
+        ].iter().map(|url| resolve_video_id(&vault, url, None)).collect();
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert_ne!(resolve_video_id(&vault, "https://youtu.be/aaaaaaaaaaa", None), ids[0]);
+    }
+
+    #[test]
+    fn unsupported_audio_format_is_rejected_before_downloading() {
+        let options = PipelineOptions { audio_format: Some("aiff".to_string()), ..Default::default() };
+        let error = DownloadOptions::from_pipeline_options(&options).validate().unwrap_err();
+        assert!(matches!(error, PipelineError::InvalidInput(message) if message.contains("aiff")));
+        
+        let options = PipelineOptions { audio_format: Some("mp3".to_string()), ..Default::default() };
+        assert!(DownloadOptions::from_pipeline_options(&options).validate().is_ok());
+    }
+
+    #[test]
+    fn parses_gemini_generate_content_response() {
+        let body = r#"{
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{"text": "总结：\n"}, {"text": "视频介绍了 Rust。"}]
+                },
+                "finishReason": "STOP",
+                "index": 0
+            }],
+            "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 8, "totalTokenCount": 20}
+        }"#;
+        assert_eq!(parse_gemini_response(body).unwrap(), "总结：\n视频介绍了 Rust。");
+        // 被安全策略拦截时没有 candidates
+        assert!(parse_gemini_response(r#"{"promptFeedback": {"blockReason": "SAFETY"}}"#).is_err());
+    }
+
+    #[test]
+    fn parses_yt_dlp_progress_lines() {
+        assert_eq!(parse_download_percent("[download]   0.0% of   12.34MiB at  Unknown B/s ETA Unknown"), Some(0.0));
+        assert_eq!(parse_download_percent("[download]  42.7% of ~  3.21MiB at  1.02MiB/s ETA 00:02 (frag 3/10)"), Some(42.7));
+        assert_eq!(parse_download_percent("[download] 100% of   12.34MiB in 00:00:05 at 2.31MiB/s"), Some(100.0));
+        assert_eq!(parse_download_percent("[download] Destination: /tmp/video.webm"), None);
+        assert_eq!(parse_download_percent("[youtube] dQw4w9WgXcQ: Downloading webpage"), None);
+    }
+
+    #[test]
+    fn concurrent_vault_updates_lose_no_writes() {
+        const UPDATES_PER_THREAD: usize = 20;
+        let base = tempfile::tempdir().unwrap();
+        let vault_path = base.path().join("video-transcriber-vault");
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        
+        let writers: Vec<_> = ["a", "b"].into_iter()
+            .map(|writer| {
+                let (vault_path, barrier) = (vault_path.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    for index in 0..UPDATES_PER_THREAD {
+                        let id = format!("{}{}", writer, index);
+                        update_vault(&vault_path, |vault| {
+                            vault.videos.insert(id.clone(), test_record(&id, &format!("https://example.com/{}", id)));
+                        }).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        
+        let vault = load_vault(&vault_path).unwrap();
+        assert_eq!(vault.videos.len(), 2 * UPDATES_PER_THREAD);
+        assert!(vault.videos.contains_key("a0") && vault.videos.contains_key(&format!("b{}", UPDATES_PER_THREAD - 1)));
+    }
+
+    #[test]
+    fn expands_tilde_with_unix_and_windows_home_variables() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| std::ffi::OsString::from(value))
+        };
+        
+        let unix = env(&[("HOME", "/home/alice")]);
+        assert_eq!(expand_tilde_path_with("~", unix), "/home/alice");
+        assert_eq!(expand_tilde_path_with("~/Downloads", unix), Path::new("/home/alice").join("Downloads").to_string_lossy());
+        assert_eq!(expand_tilde_path_with("~bob/Downloads", unix), "~bob/Downloads");
+        assert_eq!(expand_tilde_path_with("/tmp/~", unix), "/tmp/~");
+        
+        // Windows 上没有 HOME，依次使用 USERPROFILE、HOMEDRIVE + HOMEPATH
+        let windows = env(&[("HOME", ""), ("USERPROFILE", r"C:\Users\alice")]);
+        assert_eq!(expand_tilde_path_with(r"~\Downloads", windows), Path::new(r"C:\Users\alice").join("Downloads").to_string_lossy());
+        let windows = env(&[("HOMEDRIVE", "D:"), ("HOMEPATH", r"\Users\alice")]);
+        assert_eq!(expand_tilde_path_with("~", windows), r"D:\Users\alice");
+        
+        assert_eq!(expand_tilde_path_with("~/Downloads", env(&[])), "~/Downloads");
+    }
+
+    #[test]
+    fn newest_complete_audio_file_is_chosen() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = std::time::SystemTime::now();
+        let files = [
+            ("old.wav", now - std::time::Duration::from_secs(60)),
+            ("new.wav", now - std::time::Duration::from_secs(30)),
+            ("partial.wav.part", now),
+        ];
+        for (name, modified) in files {
+            let path = dir.path().join(name);
+            fs::write(&path, "audio").unwrap();
+            fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        }
+        
+        let found = find_audio_file(dir.path(), None).unwrap();
+        assert!(found.path.ends_with("new.wav"));
+        assert_eq!(found.other_candidates.len(), 1);
+        assert!(found.other_candidates[0].ends_with("old.wav"));
+        assert!(found.ambiguity_warning().is_some());
+    }
+
+    #[test]
+    fn parses_whisper_json_segments() {
+        let body = r#"{
+            "text": " Hello there. General Kenobi.",
+            "segments": [
+                {"id": 0, "seek": 0, "start": 0.0, "end": 2.5, "text": " Hello there.", "tokens": [50364, 2425], "temperature": 0.0, "avg_logprob": -0.21, "compression_ratio": 0.8, "no_speech_prob": 0.01},
+                {"id": 1, "seek": 0, "start": 2.5, "end": 4.75, "text": " General Kenobi.", "tokens": [50489, 6996], "temperature": 0.0, "avg_logprob": -0.35, "compression_ratio": 0.8, "no_speech_prob": 0.02}
+            ]
+        }"#;
+        let parsed = parse_whisper_json(TranscriptionBackend::PythonWhisper, body).unwrap();
+        assert_eq!(parsed.segments.len(), 2);
+        assert_eq!((parsed.segments[1].start, parsed.segments[1].end), (2.5, 4.75));
+        assert_eq!(parsed.segments[0].text, " Hello there.");
+        assert_eq!(parsed.segments[0].avg_logprob, Some(-0.21));
+        assert!(parse_whisper_json(TranscriptionBackend::PythonWhisper, "{}").is_err());
+    }
+
+    #[test]
+    fn transcript_lookup_prefers_the_file_matching_the_audio_stem() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["first.txt", "second.txt"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let audio = dir.path().join("second.wav");
+        let found = find_transcript_file(&audio.to_string_lossy(), dir.path(), "txt").unwrap();
+        assert!(found.ends_with("second.txt"));
+        
+        // 没有同名文件且候选不唯一时不猜测
+        let other = dir.path().join("third.wav");
+        assert_eq!(find_transcript_file(&other.to_string_lossy(), dir.path(), "txt"), None);
+    }
+
+    #[test]
+    fn summary_defaults_apply_when_max_tokens_and_temperature_are_unset() {
+        let options = SummaryOptions::from_pipeline_options(&PipelineOptions::default(), None).unwrap();
+        assert_eq!(options.max_tokens, DEFAULT_SUMMARY_MAX_TOKENS);
+        assert_eq!(options.temperature, DEFAULT_SUMMARY_TEMPERATURE);
+        assert!(options.validate().is_ok());
+        
+        let options = PipelineOptions { max_tokens: Some(1200), temperature: Some(0.2), ..Default::default() };
+        let options = SummaryOptions::from_pipeline_options(&options, None).unwrap();
+        assert_eq!((options.max_tokens, options.temperature), (1200, 0.2));
+    }
+
+    #[test]
+    fn reads_detected_language_from_whisper_json() {
+        let body = r#"{"text": " 你好。", "segments": [{"start": 0.0, "end": 1.0, "text": " 你好。"}], "language": "ZH "}"#;
+        let parsed = parse_whisper_json(TranscriptionBackend::PythonWhisper, body).unwrap();
+        assert_eq!(parsed.language.as_deref(), Some("zh"));
+        
+        let body = r#"{"result": {"language": "ja"}, "transcription": [{"offsets": {"from": 0, "to": 1500}, "text": " こんにちは"}]}"#;
+        let parsed = parse_whisper_json(TranscriptionBackend::WhisperCpp, body).unwrap();
+        assert_eq!(parsed.language.as_deref(), Some("ja"));
+        assert_eq!(parsed.segments[0].end, 1.5);
+        
+        let body = r#"{"segments": [], "language": ""}"#;
+        assert_eq!(parse_whisper_json(TranscriptionBackend::PythonWhisper, body).unwrap().language, None);
+    }
+
+    #[test]
+    fn records_with_the_same_audio_hash_share_a_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut original = test_record("original", "https://example.com/a");
+        original.transcribed = true;
+        original.audio_sha256 = Some("same-hash".to_string());
+        original.transcript_content = Some("共享的转录文本".to_string());
+        original.whisper_model = Some(DEFAULT_WHISPER_MODEL.to_string());
+        let mut reupload = test_record("reupload", "https://example.org/b");
+        reupload.audio_sha256 = Some("same-hash".to_string());
+        let mut unrelated = test_record("unrelated", "https://example.net/c");
+        unrelated.audio_sha256 = Some("other-hash".to_string());
+        let vault = Vault {
+            version: VAULT_VERSION,
+            videos: [original, reupload.clone(), unrelated.clone()].into_iter().map(|record| (record.id.clone(), record)).collect(),
+        };
+        let options = TranscribeOptions { output_dir: Some(dir.path().to_path_buf()), ..TranscribeOptions::from_pipeline_options(&PipelineOptions::default()) };
+        
+        let source = find_duplicate_transcription(&vault, &reupload, &options).unwrap();
+        assert_eq!(source.id, "original");
+        assert!(find_duplicate_transcription(&vault, &unrelated, &options).is_none());
+        
+        let audio = dir.path().join("audio.wav").to_string_lossy().to_string();
+        let output = copy_transcription(&source, &audio, &options).unwrap();
+        assert_eq!(output.content, "共享的转录文本");
+        assert_eq!(fs::read_to_string(&output.transcript_file).unwrap(), "共享的转录文本\n");
+        
+        // 指定了不同的模型时不复用
+        let options = TranscribeOptions { model: "large".to_string(), ..options };
+        assert!(find_duplicate_transcription(&vault, &reupload, &options).is_none());
+    }
+
+    #[test]
+    fn fallback_keyword_extractor_ranks_repeated_terms() {
+        let text = "Rust makes systems programming safe. Rust ownership prevents data races, \
+            and ownership rules are checked by the compiler. The compiler is strict about ownership.";
+        assert_eq!(extract_keywords_by_frequency(text, 2), ["ownership", "rust"]);
+        assert_eq!(extract_keywords_by_frequency(text, 10), ["ownership", "rust", "compiler"]);
+        
+        // 中文按相邻两字统计，只出现一次的不算关键词
+        let text = "机器学习很有用。我们学习机器学习的方法，机器学习需要数据。";
+        assert_eq!(extract_keywords_by_frequency(text, 2), ["学习", "机器"]);
+        assert!(extract_keywords_by_frequency("no repeated words here", 5).is_empty());
+    }
+
+    #[test]
+    fn colliding_video_ids_are_salted_instead_of_overwritten() {
+        let url = "https://example.com/b";
+        let taken_id = hash_video_key(&video_key(url, None));
+        // 模拟另一个视频的 16 位 id 恰好相同
+        let mut vault = Vault { version: VAULT_VERSION, videos: HashMap::new() };
+        vault.videos.insert(taken_id.clone(), test_record(&taken_id, "https://example.com/a"));
+        
+        let salted_id = resolve_video_id(&vault, url, None);
+        assert_ne!(salted_id, taken_id);
+        assert_eq!(salted_id.len(), taken_id.len());
+        vault.videos.insert(salted_id.clone(), test_record(&salted_id, url));
+        
+        // 之后按链接找回加盐的 id，先占用的记录删除后也不变
+        assert_eq!(resolve_video_id(&vault, url, None), salted_id);
+        vault.videos.remove(&taken_id);
+        assert_eq!(resolve_video_id(&vault, url, None), salted_id);
+        assert_eq!(vault.videos[&salted_id].url, url);
+    }
+
+    #[tokio::test]
+    async fn previous_vault_survives_a_partial_write() {
+        let base = tempfile::tempdir().unwrap();
+        let vault_path = base.path().join("video-transcriber-vault");
+        save_record(&vault_path, &test_record("abc", "https://example.com/a")).await.unwrap();
+        let config_path = get_vault_config_path(&vault_path);
+        let good = fs::read_to_string(&config_path).unwrap();
+        
+        // 进程在写临时文件途中被杀死：只留下半截的 .tmp，vault.toml 不受影响
+        fs::write(config_path.with_file_name("vault.toml.tmp"), &good[..good.len() / 2]).unwrap();
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), good);
+        assert!(load_vault(&vault_path).unwrap().videos.contains_key("abc"));
+        
+        // 临时文件无法写入时目标文件保持原样
+        fs::remove_file(config_path.with_file_name("vault.toml.tmp")).unwrap();
+        fs::create_dir(config_path.with_file_name("vault.toml.tmp")).unwrap();
+        assert!(write_file_atomically(&config_path, "broken = ").is_err());
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), good);
+    }
+
+    #[test]
+    fn detects_directories_not_referenced_by_any_record() {
+        let base = tempfile::tempdir().unwrap();
+        let vault_path = base.path().join("video-transcriber-vault");
+        for dir in ["abc", "orphan", ".recordings/123", "running"] {
+            fs::create_dir_all(vault_path.join(dir)).unwrap();
+        }
+        fs::write(vault_path.join("orphan").join("audio.wav"), "12345").unwrap();
+        let vault = Vault {
+            version: VAULT_VERSION,
+            videos: HashMap::from([("abc".to_string(), test_record("abc", "https://example.com/a"))]),
+        };
+        let running = HashSet::from(["running".to_string()]);
+        
+        let orphans = find_orphan_dirs(&vault_path, &vault, &running).unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].path, "orphan");
+        assert_eq!(orphans[0].size_bytes, 5);
+    }
+
+    #[tokio::test]
+    async fn purging_audio_removes_the_file_and_marks_the_record() {
+        let base = tempfile::tempdir().unwrap();
+        let vault_path = base.path().join("video-transcriber-vault");
+        let audio = base.path().join("audio.wav");
+        fs::write(&audio, "audio").unwrap();
+        let mut record = test_record("abc", "https://example.com/a");
+        record.downloaded = true;
+        record.transcribed = true;
+        record.audio_file = Some(audio.to_string_lossy().to_string());
+        
+        purge_audio_file(&mut record).unwrap();
+        save_record(&vault_path, &record).await.unwrap();
+        
+        assert!(!audio.exists());
+        let saved = &load_vault(&vault_path).unwrap().videos["abc"];
+        assert!(saved.downloaded && saved.audio_purged);
+        assert_eq!(saved.audio_file, None);
+        
+        // 文件删除失败时记录不变
+        let mut record = test_record("def", "https://example.com/d");
+        record.audio_file = Some(base.path().join("missing.wav").to_string_lossy().to_string());
+        assert!(purge_audio_file(&mut record).is_err());
+        assert!(record.audio_file.is_some() && !record.audio_purged);
+    }
+
+    /// 用脚本模拟 yt-dlp：带 --continue 运行时把上次中断留下的 .part 文件补全
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn interrupted_part_file_is_completed_on_next_download() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("video");
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(output_dir.join("标题.mp3.part"), "前半段").unwrap();
+        
+        let script = dir.path().join("yt-dlp");
+        fs::write(&script, format!(r#"#!/bin/sh
+case "$*" in
+    *--version*) echo 2024.01.01 ;;
+    *--no-download*) printf '60\n20240101\nuploader\n"标题"\n' ;;
+    *--continue*)
+        part="{0}/标题.mp3.part"
+        [ -f "$part" ] || exit 1
+        printf '后半段' >> "$part" && mv "$part" "{0}/标题.mp3"
+        echo "[download] 100.0% of 1.00MiB" ;;
+    *) exit 1 ;;
+esac
+"#, output_dir.display())).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        
+        // 中断时目录里只有 .part 文件，不能当成下载完成的音频
+        assert!(find_audio_file(&output_dir, Some("mp3")).is_none());
+        
+        let options = DownloadOptions::from_pipeline_options(&PipelineOptions {
+            yt_dlp_path: Some(script.to_string_lossy().to_string()),
+            audio_format: Some("mp3".to_string()),
+            ..Default::default()
+        });
+        let downloaded = download_video_to_dir("https://example.com/watch?v=1", &output_dir, &options, |_, _| {}, &CancelToken::new())
+            .await
+            .unwrap();
+        
+        assert_eq!(downloaded.title, "标题");
+        assert_eq!(PathBuf::from(&downloaded.audio_file), output_dir.join("标题.mp3"));
+        assert_eq!(fs::read_to_string(&downloaded.audio_file).unwrap(), "前半段后半段");
+        assert!(!output_dir.join("标题.mp3.part").exists());
+    }
+
+    #[test]
+    fn multiline_title_is_collapsed_to_single_line() {
+        // --print %(title)j 会把换行转义，JSON 解析后再合并空白
+        assert_eq!(parse_printed_title(r#""第一行\n第二行\r\n  第三行""#).as_deref(), Some("第一行 第二行 第三行"));
+        // 未转义的原始标题也按同样规则处理
+        assert_eq!(parse_printed_title("  Part 1:\tIntro  ").as_deref(), Some("Part 1: Intro"));
+        assert_eq!(parse_printed_title(r#"" \n ""#), None);
+        assert_eq!(parse_printed_title("NA"), None);
+        
+        let title = normalize_title("标题\n第二行");
+        let file_name = sanitize_file_name(&title).unwrap();
+        assert_eq!(file_name, "标题 第二行");
+        assert!(!file_name.contains('\n'));
+    }
+
+    #[test]
+    fn simple_summary_selects_sentences_with_frequent_terms() {
+        let transcript = "Rust ownership makes memory safe. The weather was nice today. Rust ownership prevents data races. We ate lunch.";
+        assert_eq!(
+            select_key_sentences(transcript, 2),
+            vec!["Rust ownership makes memory safe.", "Rust ownership prevents data races."]
+        );
+        // 句子不足时全部保留，顺序与原文一致
+        assert_eq!(select_key_sentences("Second point here. First point here.", 5), vec!["Second point here.", "First point here."]);
+        assert!(select_key_sentences("", 3).is_empty());
+        
+        let summary = generate_simple_summary(transcript, false);
+        // 默认选 3 句，得分最低的 "We ate lunch." 被舍去
+        assert!(summary.contains("Rust ownership makes memory safe. The weather was nice today. Rust ownership prevents data races."));
+        assert!(!summary.contains("lunch"));
+        assert!(!summary.contains("API"));
+        assert!(generate_simple_summary(transcript, true).contains("API"));
+    }
+
+    #[test]
+    fn sentences_are_split_and_joined_by_language() {
+        let english = "Pi is 3.14 roughly. Is that right? Yes!";
+        let sentences = split_sentences(english);
+        assert_eq!(sentences, vec!["Pi is 3.14 roughly.", "Is that right?", "Yes!"]);
+        assert!(!is_mostly_cjk(english));
+        assert_eq!(join_sentences(&sentences, false), english);
+        assert_eq!(join_sentences(&["no ending".to_string(), "next".to_string()], false), "no ending. next.");
+        assert_eq!(count_words(english), 8);
+        
+        let chinese = "今天天气很好。我们去公园吧！你想去吗？";
+        let sentences = split_sentences(chinese);
+        assert_eq!(sentences, vec!["今天天气很好。", "我们去公园吧！", "你想去吗？"]);
+        assert!(is_mostly_cjk(chinese));
+        assert_eq!(join_sentences(&sentences, true), chinese);
+        assert_eq!(join_sentences(&["没有标点".to_string()], true), "没有标点。");
+        // 中文没有空格分词，按字计数
+        assert_eq!(count_words(chinese), 16);
+    }
+
+    #[test]
+    fn unversioned_vault_is_migrated_to_current_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault_path = dir.path().to_path_buf();
+        // 早期版本写出的 vault.toml：没有版本号，记录也只有最初的几个字段
+        fs::write(get_vault_config_path(&vault_path), r#"
+[videos.abc123]
+id = "abc123"
+url = "https://example.com/watch?v=1"
+title = "旧记录"
+downloaded = true
+transcribed = true
+summarized = false
+transcript_content = "转录"
+created_at = "2024-01-01T00:00:00Z"
+updated_at = "2024-01-01T00:00:00Z"
+"#).unwrap();
+        
+        let vault = load_vault(&vault_path).unwrap();
+        assert_eq!(vault.version, VAULT_VERSION);
+        let record = &vault.videos["abc123"];
+        assert_eq!(record.title.as_deref(), Some("旧记录"));
+        assert_eq!(record.transcript_content.as_deref(), Some("转录"));
+        assert!(record.subtitle_files.is_empty());
+        assert_eq!(record.duration_seconds, None);
+        
+        // 升级后的内容在保存时写回
+        save_vault(&vault_path, &vault).unwrap();
+        let content = fs::read_to_string(get_vault_config_path(&vault_path)).unwrap();
+        assert_eq!(toml::from_str::<Vault>(&content).unwrap().version, VAULT_VERSION);
+        
+        let newer = Vault { version: VAULT_VERSION + 1, videos: HashMap::new() };
+        assert!(matches!(migrate_vault(newer), Err(PipelineError::VaultIo(_))));
+    }
+
+    #[test]
+    fn transcript_is_formatted_into_paragraphs() {
+        // whisper 按时间切行，一句话可能跨行，行内也可能有多余空白
+        let raw = "One.   Two.\nThree\ncontinues. Four. Five. Six.\n\n  Seven.\n";
+        assert_eq!(format_transcript(raw), "One. Two. Three continues. Four. Five.\n\nSix. Seven.");
+        
+        // 中文跨行的句子直接相连，不插入空格
+        assert_eq!(format_transcript("第一句。第二\n句。\n"), "第一句。第二句。");
+        assert_eq!(format_transcript("\n  \n"), "");
+    }
+
+    #[test]
+    fn download_stderr_is_mapped_to_error_kind() {
+        let kind = |stderr: &str| download_failure("下载失败".to_string(), stderr);
+        assert!(matches!(
+            kind("ERROR: [youtube] abc123: Sign in to confirm your age. This video may be inappropriate for some users."),
+            PipelineError::AgeRestricted(_)
+        ));
+        assert!(matches!(
+            kind("ERROR: [youtube] abc123: The uploader has not made this video available in your country"),
+            PipelineError::GeoBlocked(_)
+        ));
+        assert!(matches!(
+            kind("ERROR: [BiliBili] 12345: This video is GEO-RESTRICTED"),
+            PipelineError::GeoBlocked(_)
+        ));
+        assert!(matches!(
+            kind("ERROR: [youtube] abc123: Video unavailable. This video has been removed by the uploader"),
+            PipelineError::DownloadFailed(_)
+        ));
+        // 错误信息原样保留，供前端展示
+        assert!(matches!(kind("age-restricted"), PipelineError::AgeRestricted(message) if message == "下载失败"));
+        
+        let options = DownloadOptions::from_pipeline_options(&PipelineOptions {
+            geo_bypass: true,
+            age_limit: Some(18),
+            ..Default::default()
+        });
+        let command = build_download_command("https://example.com/watch?v=1", Path::new("/tmp"), &options);
+        let args: Vec<String> = command.as_std().get_args().map(|arg| arg.to_string_lossy().to_string()).collect();
+        assert!(args.contains(&"--geo-bypass".to_string()));
+        assert!(args.windows(2).any(|pair| pair == ["--age-limit", "18"]));
+    }
+
+    #[test]
+    fn translate_task_is_passed_to_whisper() {
+        let args = |command: &tokio::process::Command| -> Vec<String> {
+            command.as_std().get_args().map(|arg| arg.to_string_lossy().to_string()).collect()
+        };
+        
+        let options = TranscribeOptions::from_pipeline_options(&PipelineOptions::default());
+        options.validate().unwrap();
+        assert!(args(&build_python_whisper_command("whisper", "/tmp/audio.wav", &options)).windows(2).any(|pair| pair == ["--task", "transcribe"]));
+        
+        let options = TranscribeOptions::from_pipeline_options(&PipelineOptions {
+            task: Some(" Translate ".to_string()),
+            ..Default::default()
+        });
+        options.validate().unwrap();
+        assert!(args(&build_python_whisper_command("whisper", "/tmp/audio.wav", &options)).windows(2).any(|pair| pair == ["--task", "translate"]));
+        
+        // whisper.cpp 用 -tr 开启翻译
+        let dir = tempfile::tempdir().unwrap();
+        let model = dir.path().join("ggml-base.bin");
+        fs::write(&model, "model").unwrap();
+        let options = TranscribeOptions {
+            whisper_cpp_model: Some(model.to_string_lossy().to_string()),
+            ..options
+        };
+        assert!(args(&build_whisper_cpp_command("whisper-cli", "/tmp/audio.wav", &options).unwrap()).contains(&"-tr".to_string()));
+        
+        let options = TranscribeOptions::from_pipeline_options(&PipelineOptions {
+            task: Some("summarize".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(options.validate(), Err(PipelineError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn emoji_and_cjk_title_round_trips_through_vault() {
+        let title = "🎬 中文标题：日本語タイトル 🚀";
+        // yt-dlp 的 %(title)j 默认把非 ASCII 字符转义为 \uXXXX，emoji 为一对代理项
+        let escaped: String = title.encode_utf16()
+            .map(|unit| if unit < 0x80 { char::from(unit as u8).to_string() } else { format!("\\u{:04x}", unit) })
+            .collect();
+        assert!(escaped.is_ascii());
+        let printed = format!("\u{FEFF}\"{}\"\n", escaped);
+        let parsed = parse_printed_title(&decode_tool_output(printed.as_bytes())).unwrap();
+        assert_eq!(parsed, title);
+        assert_eq!(sanitize_file_name(&parsed).as_deref(), Some("🎬 中文标题：日本語タイトル 🚀"));
+        
+        let dir = tempfile::tempdir().unwrap();
+        let vault_path = dir.path().to_path_buf();
+        let mut vault = load_vault(&vault_path).unwrap();
+        vault.videos.insert("abc123".to_string(), VideoRecord {
+            title: Some(parsed),
+            ..test_record("abc123", "https://example.com/watch?v=1")
+        });
+        save_vault(&vault_path, &vault).unwrap();
+        
+        // 绕过缓存，从文件重新解析
+        cache_vault(&vault_path, None);
+        let vault = load_vault(&vault_path).unwrap();
+        assert_eq!(vault.videos["abc123"].title.as_deref(), Some(title));
+        assert!(fs::read_to_string(get_vault_config_path(&vault_path)).unwrap().contains(title));
+    }
+
+    #[test]
+    fn low_confidence_segments_are_flagged_or_dropped() {
+        let segment = |text: &str, avg_logprob: Option<f64>| TranscriptSegment {
+            start: 0.0,
+            end: 1.0,
+            text: text.to_string(),
+            speaker: None,
+            avg_logprob,
+            no_speech_prob: None,
+            low_confidence: false,
+        };
+        let segments = vec![
+            segment("可靠", Some(-0.2)),
+            segment("可疑", Some(-1.5)),
+            segment("边界", Some(-1.0)),
+            segment("乱码", Some(-2.3)),
+        ];
+        
+        let mut flagged = segments.clone();
+        let warning = apply_confidence_threshold(&mut flagged, -1.0, false).unwrap();
+        assert!(warning.starts_with("2/4"));
+        let low: Vec<&str> = flagged.iter().filter(|segment| segment.low_confidence).map(|segment| segment.text.as_str()).collect();
+        assert_eq!(low, vec!["可疑", "乱码"]);
+        assert_eq!(flagged.len(), 4);
+        
+        let mut dropped = segments.clone();
+        assert!(apply_confidence_threshold(&mut dropped, -1.0, true).unwrap().contains("2/4"));
+        let kept: Vec<&str> = dropped.iter().map(|segment| segment.text.as_str()).collect();
+        assert_eq!(kept, vec!["可靠", "边界"]);
+        
+        // 全部高于阈值时没有提示
+        let mut all_kept = segments.clone();
+        assert_eq!(apply_confidence_threshold(&mut all_kept, -3.0, true), None);
+        assert_eq!(all_kept.len(), 4);
+        
+        // 没有置信度信息时不过滤，只给出提示
+        let mut unscored = vec![segment("whisper.cpp", None)];
+        assert!(apply_confidence_threshold(&mut unscored, -1.0, true).is_some());
+        assert_eq!(unscored.len(), 1);
+    }
+
+    #[test]
+    fn saved_vault_is_seen_by_cached_reads() {
+        let cached_ids = |vault_path: &Path| -> Option<Vec<String>> {
+            let cache = VAULT_CACHE.lock().unwrap();
+            let mut ids: Vec<String> = cache.as_ref()?.get(vault_path)?.vault.videos.keys().cloned().collect();
+            ids.sort();
+            Some(ids)
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let vault_path = dir.path().join("a");
+        let other_path = dir.path().join("b");
+        
+        update_vault(&vault_path, |vault| {
+            vault.videos.insert("first".to_string(), test_record("first", "https://example.com/1"));
+        }).unwrap();
+        assert_eq!(cached_ids(&vault_path), Some(vec!["first".to_string()]));
+        
+        // 保存同时更新磁盘和缓存，之后的读取直接命中缓存并看到新记录
+        update_vault(&vault_path, |vault| {
+            vault.videos.insert("second".to_string(), test_record("second", "https://example.com/2"));
+        }).unwrap();
+        assert_eq!(cached_ids(&vault_path), Some(vec!["first".to_string(), "second".to_string()]));
+        assert_eq!(load_vault(&vault_path).unwrap().videos.len(), 2);
+        
+        // 不同的 vault 目录各自缓存
+        save_vault(&other_path, &Vault { version: VAULT_VERSION, videos: HashMap::new() }).unwrap();
+        assert!(load_vault(&other_path).unwrap().videos.is_empty());
+        assert_eq!(load_vault(&vault_path).unwrap().videos.len(), 2);
+        
+        // 绕过 save_vault 的写入（如另一个窗口）改变了文件内容，缓存失效后重新解析
+        let mut external = load_vault(&vault_path).unwrap();
+        external.videos.remove("first");
+        fs::write(get_vault_config_path(&vault_path), toml::to_string_pretty(&external).unwrap()).unwrap();
+        let vault = load_vault(&vault_path).unwrap();
+        assert_eq!(vault.videos.keys().collect::<Vec<_>>(), vec!["second"]);
+        assert_eq!(cached_ids(&vault_path), Some(vec!["second".to_string()]));
+    }
+
+    #[test]
+    fn relocate_vault_dry_run_then_copy_keeps_backup_and_rebases_paths() {
+        let base = tempfile::tempdir().unwrap();
+        let from = base.path().join("old").join("video-transcriber-vault");
+        let to = base.path().join("new").join("video-transcriber-vault");
+        
+        let video_dir = from.join("abc");
+        fs::create_dir_all(&video_dir).unwrap();
+        fs::write(video_dir.join("transcript.txt"), "hello").unwrap();
+        let mut record = test_record("abc", "https://example.com/v");
+        record.transcript_file = Some(video_dir.join("transcript.txt").to_string_lossy().to_string());
+        let mut vault = Vault { version: VAULT_VERSION, videos: HashMap::new() };
+        save_vault(&from, &vault).unwrap();
+        vault.videos.insert("abc".to_string(), record);
+        // 第二次保存生成与 vault.toml 大小不同的 vault.toml.bak
+        save_vault(&from, &vault).unwrap();
+        let backup = fs::read_to_string(from.join("vault.toml.bak")).unwrap();
+        
+        let report = relocate_vault_files(&from, &to, false, true).unwrap();
+        assert!(report.files.contains(&"vault.toml.bak".to_string()));
+        assert!(report.files.contains(&"abc/transcript.txt".to_string()));
+        assert_eq!(report.rewritten_paths, 1);
+        assert!(!to.exists());
+        
+        relocate_vault_files(&from, &to, false, false).unwrap();
+        assert_eq!(fs::read_to_string(to.join("vault.toml.bak")).unwrap(), backup);
+        assert_eq!(fs::read_to_string(to.join("abc").join("transcript.txt")).unwrap(), "hello");
+        let relocated = load_vault(&to).unwrap();
+        let transcript_file = relocated.videos["abc"].transcript_file.clone().unwrap();
+        assert_eq!(PathBuf::from(transcript_file), to.join("abc").join("transcript.txt"));
+        assert!(from.join("vault.toml").is_file());
+        
+        let moved_to = base.path().join("moved").join("video-transcriber-vault");
+        relocate_vault_files(&from, &moved_to, true, false).unwrap();
+        assert!(!from.exists());
+        assert!(moved_to.join("abc").join("transcript.txt").is_file());
+    }
+
+    #[test]
+    fn playlist_id_differs_from_its_first_video() {
+        let url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1234567890";
+        assert_ne!(playlist_pipeline_id(url), hash_video_key(&video_key(url, None)));
+    }
+
+    #[test]
+    fn single_url_batch_id_differs_from_the_video_id() {
+        let urls = vec!["https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string()];
+        assert_ne!(batch_pipeline_id(&urls), hash_video_key(&video_key(&urls[0], None)));
+    }
+
+    #[test]
+    fn cancelled_download_cleanup_keeps_existing_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["transcript.txt", "video.en.srt", "video.webm.part", "video.temp.m4a", "video.f137.mp4"] {
+            fs::write(dir.path().join(name), "data").unwrap();
+        }
+        remove_download_leftovers(dir.path());
+        let mut left: Vec<String> = fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["transcript.txt", "video.en.srt"]);
+    }
+
+    #[test]
+    fn repair_restores_a_missing_transcript_from_the_record() {
+        let base = tempfile::tempdir().unwrap();
+        let base_path = base.path().to_string_lossy().to_string();
+        let vault_path = resolve_vault_path(Some(base_path.clone()));
+        let transcript_file = vault_path.join("abc").join("audio.txt");
+        let mut restorable = test_record("abc", "https://example.com/a");
+        restorable.transcribed = true;
+        restorable.transcript_file = Some(transcript_file.to_string_lossy().to_string());
+        restorable.transcript_content = Some("转录内容".to_string());
+        let mut lost = test_record("def", "https://example.com/d");
+        lost.transcribed = true;
+        lost.summarized = true;
+        lost.transcript_file = Some(vault_path.join("def").join("audio.txt").to_string_lossy().to_string());
+        update_vault(&vault_path, |vault| {
+            vault.videos.insert("abc".to_string(), restorable);
+            vault.videos.insert("def".to_string(), lost);
+        }).unwrap();
+        
+        repair_vault(Some(base_path)).unwrap();
+        
+        assert_eq!(fs::read_to_string(&transcript_file).unwrap(), "转录内容");
+        let vault = load_vault(&vault_path).unwrap();
+        assert!(vault.videos["abc"].transcribed);
+        let lost = &vault.videos["def"];
+        assert!(!lost.transcribed && !lost.summarized && lost.transcript_file.is_none());
+    }
+
+    #[test]
+    fn same_size_rewrite_with_the_same_mtime_is_not_served_from_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault_path = dir.path().to_path_buf();
+        update_vault(&vault_path, |vault| {
+            vault.videos.insert("abc".to_string(), test_record("abc", "https://example.com/a"));
+        }).unwrap();
+        let config_path = get_vault_config_path(&vault_path);
+        let modified = fs::metadata(&config_path).unwrap().modified().unwrap();
+        assert!(load_vault(&vault_path).unwrap().videos.contains_key("abc"));
+        
+        // 另一个窗口写入等长的内容，且修改时间落在同一时间粒度内
+        let rewritten = fs::read_to_string(&config_path).unwrap().replace("abc", "xyz");
+        fs::write(&config_path, rewritten).unwrap();
+        fs::File::options().write(true).open(&config_path).unwrap().set_modified(modified).unwrap();
+        
+        let vault = load_vault(&vault_path).unwrap();
+        assert_eq!(vault.videos.keys().collect::<Vec<_>>(), vec!["xyz"]);
+    }
+}
@@ -1,5 +1,6 @@
-import { useState, useEffect } from "react";
+import { useState, useEffect, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { ThemeProvider } from "@/components/theme-provider";
 import { ThemeToggle } from "@/components/theme-toggle";
 import { Button } from "@/components/ui/button";
@@ -20,6 +21,57 @@ interface ProcessStep {
   output: string[];
 }
 
+const PROVIDER_NAMES: Record<string, string> = {
+  openai: "OpenAI",
+  deepseek: "DeepSeek",
+  anthropic: "Anthropic",
+  gemini: "Gemini",
+};
+
+interface PipelineProgress {
+  video_id: string;
+  stage: string;
+  percent: number | null;
+  message: string;
+}
+
+interface PipelineStage {
+  name: "download" | "transcribe" | "summarize";
+  status: "skipped" | "pending" | "running" | "done" | "failed";
+  detail: string | null;
+}
+
+interface PipelineResult {
+  record: {
+    id: string;
+    title: string | null;
+    transcript_content: string | null;
+    summary_content: string | null;
+  };
+  stages: PipelineStage[];
+  warnings: string[];
+}
+
+const STAGE_STATUS_LABELS: Record<PipelineStage["status"], string> = {
+  skipped: "已完成，跳过",
+  pending: "待执行",
+  running: "进行中",
+  done: "完成",
+  failed: "失败",
+};
+
+interface PipelineError {
+  kind: string;
+  message: string;
+}
+
+function formatError(error: unknown): string {
+  if (typeof error === "object" && error !== null && "message" in error) {
+    return (error as PipelineError).message;
+  }
+  return String(error);
+}
+
 function AppContent() {
   const [videoUrl, setVideoUrl] = useState("");
   const [downloadPath, setDownloadPath] = useState("");
@@ -29,6 +81,8 @@ function AppContent() {
   const [status, setStatus] = useState("");
   const [transcript, setTranscript] = useState("");
   const [summary, setSummary] = useState("");
+  // 当前由界面启动的流水线的视频 id，其他流水线（播放列表、批量处理等）的进度事件不显示在步骤中
+  const activeVideoId = useRef<string | null>(null);
   const [processSteps, setProcessSteps] = useState<ProcessStep[]>([
     { id: "download", name: "下载视频", completed: false, inProgress: false, progress: 0, output: [] },
     { id: "transcribe", name: "语音转录", completed: false, inProgress: false, progress: 0, output: [] },
@@ -41,6 +95,28 @@ function AppContent() {
     setDownloadPath(defaultPath);
   }, []);
 
+  useEffect(() => {
+    // 订阅后端推送的实时进度事件
+    const unlisten = listen<PipelineProgress>("pipeline-progress", (event) => {
+      const { video_id, stage, percent, message } = event.payload;
+      if (video_id !== activeVideoId.current) return;
+      setProcessSteps(prev => prev.map(step => {
+        if (step.id !== stage) return step;
+        const progress = percent ?? step.progress;
+        return {
+          ...step,
+          progress,
+          inProgress: progress < 100,
+          completed: progress >= 100,
+          output: [...step.output, `[${new Date().toLocaleTimeString()}] ${message}`]
+        };
+      }));
+    });
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, []);
+
   async function selectDownloadPath() {
     try {
       const path = await invoke("select_download_path");
@@ -91,6 +167,11 @@ function AppContent() {
     resetSteps();
 
     try {
+      activeVideoId.current = await invoke("pipeline_video_id", {
+        url: videoUrl,
+        basePath: downloadPath || null
+      }) as string;
+      
       // 开始下载步骤
       updateStepProgress("download", 10, "开始下载视频...");
       setStatus("正在下载视频...");
@@ -103,12 +184,14 @@ function AppContent() {
       });
       
       // 解析返回的结果
-      const videoRecord = JSON.parse(result as string);
+      const pipelineResult: PipelineResult = JSON.parse(result as string);
+      const videoRecord = pipelineResult.record;
       
-      // 模拟步骤完成（由于后端是一次性返回，我们需要模拟进度）
-      updateStepProgress("download", 100, "视频下载完成", true);
-      updateStepProgress("transcribe", 100, "语音转录完成", true);
-      updateStepProgress("summarize", 100, "AI总结完成", true);
+      // 流水线已结束，按后端返回的各步骤结果更新状态
+      const stages = pipelineResult.stages;
+      stages.forEach(stage => {
+        updateStepProgress(stage.name, 100, STAGE_STATUS_LABELS[stage.status], true);
+      });
       
       // 更新UI状态
       if (videoRecord.transcript_content) {
@@ -119,12 +202,19 @@ function AppContent() {
         setSummary(videoRecord.summary_content);
       }
       
-      // 显示最终状态
+      // 显示最终状态，总结失败时转录结果仍然保留
       const title = videoRecord.title || "未知标题";
-      setStatus(`✅ 全部完成! 视频: "${title}" (ID: ${videoRecord.id})`);
+      const failedStage = stages.find(stage => stage.status === "failed");
+      const warnings = pipelineResult.warnings.length > 0 ? ` 注意: ${pipelineResult.warnings.join("；")}` : "";
+      if (failedStage) {
+        setStatus(`⚠️ 转录已完成，但总结失败: ${failedStage.detail ?? "未知错误"} 视频: "${title}" (ID: ${videoRecord.id})${warnings}`);
+      } else {
+        setStatus(`✅ 全部完成! 视频: "${title}" (ID: ${videoRecord.id})${warnings}`);
+      }
       
     } catch (error) {
-      const errorMessage = `❌ 错误: ${error}`;
+      const message = formatError(error);
+      const errorMessage = `❌ 错误: ${message}`;
       setStatus(errorMessage);
       
       // 找到当前进行中的步骤并标记为失败
@@ -133,12 +223,13 @@ function AppContent() {
           return {
             ...step,
             inProgress: false,
-            output: [...step.output, `[${new Date().toLocaleTimeString()}] 错误: ${error}`]
+            output: [...step.output, `[${new Date().toLocaleTimeString()}] 错误: ${message}`]
           };
         }
         return step;
       }));
     } finally {
+      activeVideoId.current = null;
       setIsProcessing(false);
     }
   }
@@ -208,19 +299,21 @@ function AppContent() {
               >
                 <option value="openai">OpenAI (GPT-3.5)</option>
                 <option value="deepseek">DeepSeek</option>
+                <option value="anthropic">Anthropic (Claude)</option>
+                <option value="gemini">Google Gemini</option>
               </Select>
             </div>
 
             <div className="space-y-2">
               <Label htmlFor="api-key" className="dark:text-slate-200">
-                {apiProvider === "deepseek" ? "DeepSeek" : "OpenAI"} API Key (可选)
+                {PROVIDER_NAMES[apiProvider]} API Key (可选)
               </Label>
               <Input
                 id="api-key"
                 type="password"
                 value={apiKey}
                 onChange={(e) => setApiKey(e.target.value)}
-                placeholder={`输入${apiProvider === "deepseek" ? "DeepSeek" : "OpenAI"} API密钥获得更好的AI总结，留空使用简单总结`}
+                placeholder={`输入${PROVIDER_NAMES[apiProvider]} API密钥获得更好的AI总结，留空使用简单总结`}
                 disabled={isProcessing}
                 className="dark:bg-slate-800 dark:border-slate-600 dark:text-slate-200 dark:placeholder:text-slate-500"
               />